@@ -1,4 +1,4 @@
-use std::{env, fs, io};
+use std::{env, fs};
 use std::path::PathBuf;
 
 use image::imageops::FilterType;
@@ -31,16 +31,26 @@ fn main() -> Result<(), String> {
 	
 	if cfg!(target_os = "windows") {
 		png_to_icon(ico_src, icon_path);
-		
-		WindowsResource::new()
-			// This path can be absolute, or relative to your crate root.
-			.set_icon(icon_path)
-			.compile().map_err(|err| err.to_string())?;
+		windows_resource(icon_path)?;
 	}
 	
 	Ok(())
 }
 
+/// winres is only a build dependency on Windows, so it can't be named anywhere else
+#[cfg(windows)]
+fn windows_resource(icon_path: &str) -> Result<(), String> {
+	WindowsResource::new()
+		// This path can be absolute, or relative to your crate root.
+		.set_icon(icon_path)
+		.compile().map_err(|err| err.to_string())
+}
+
+#[cfg(not(windows))]
+fn windows_resource(_icon_path: &str) -> Result<(), String> {
+	Ok(())
+}
+
 fn png_to_icon(ico_src: &str, icon_path: &str) {
 	if fs::metadata(icon_path).map(|meta| {
 		fs::metadata(ico_src).unwrap().modified().unwrap() > meta.modified().unwrap()
@@ -0,0 +1,112 @@
+use std::{env, process};
+use std::path::{Path, PathBuf};
+
+/// What the actions of a context menu work on
+pub struct Context {
+	/// The open folder
+	pub dir: PathBuf,
+	pub selection: Vec<PathBuf>,
+	/// The listing is a search result and not a folder new files can go to
+	pub isVirtual: bool,
+}
+
+impl Context {
+	pub fn hasSelection(&self) -> bool {
+		!self.selection.is_empty()
+	}
+	
+	/// The selected files, or the open folder if nothing is selected
+	pub fn targets(&self) -> Vec<PathBuf> {
+		if self.selection.is_empty() { vec![self.dir.clone()] } else { self.selection.clone() }
+	}
+	
+	/// The only selected folder, or the open one
+	pub fn folder(&self) -> Option<PathBuf> {
+		match self.selection.as_slice() {
+			[one] if one.is_dir() => { Some(one.clone()) }
+			[] if !self.isVirtual => { Some(self.dir.clone()) }
+			_ => { None }
+		}
+	}
+}
+
+pub struct Action {
+	pub id: &'static str,
+	pub label: String,
+	/// Only shown as a hint, the keys themselves are handled by the window
+	pub shortcut: &'static str,
+	/// Actions of different groups are separated in the menu
+	pub group: u32,
+	pub enabled: Box<dyn Fn(&Context) -> bool>,
+	pub run: Box<dyn Fn(&Context)>,
+}
+
+/// The actions of the context menu of one window, in the order they are shown
+#[derive(Default)]
+pub struct Registry {
+	actions: Vec<Action>,
+}
+
+impl Registry {
+	/// Adds the action at the end of its group, or replaces the one with the same id
+	pub fn register(&mut self, action: Action) {
+		if let Some(existing) = self.actions.iter_mut().find(|a| a.id == action.id) {
+			*existing = action;
+			return;
+		}
+		let at = self.actions.iter().position(|a| a.group > action.group).unwrap_or(self.actions.len());
+		self.actions.insert(at, action);
+	}
+	
+	pub fn actions(&self) -> &[Action] {
+		&self.actions
+	}
+	
+	/// False if there is no such action or it is not enabled for the context
+	pub fn run(&self, id: &str, ctx: &Context) -> bool {
+		match self.actions.iter().find(|a| a.id == id) {
+			Some(a) if (a.enabled)(ctx) => {
+				(a.run)(ctx);
+				true
+			}
+			_ => { false }
+		}
+	}
+}
+
+pub fn openTerminal(dir: &Path) -> Result<(), String> {
+	spawnTerminal(dir, &[])
+}
+
+/// Runs the command, or a shell if it is empty, in $TERMINAL or the first terminal emulator that can be started
+#[cfg(not(windows))]
+pub fn spawnTerminal(dir: &Path, command: &[String]) -> Result<(), String> {
+	let mut candidates: Vec<String> = env::var("TERMINAL").ok().filter(|t| !t.is_empty()).into_iter().collect();
+	candidates.extend(["x-terminal-emulator", "gnome-terminal", "konsole", "xfce4-terminal", "alacritty", "kitty", "foot", "wezterm", "xterm"]
+		.iter().map(|s| s.to_string()));
+	
+	for terminal in candidates {
+		// How the terminals want to be told what to run
+		let prefix: &[&str] = match terminal.as_str() {
+			_ if command.is_empty() => { &[] }
+			"gnome-terminal" => { &["--"] }
+			"kitty" | "foot" => { &[] }
+			"wezterm" => { &["start", "--"] }
+			"xfce4-terminal" => { &["-x"] }
+			_ => { &["-e"] }
+		};
+		match process::Command::new(&terminal).args(prefix).args(command).current_dir(dir).spawn() {
+			Ok(_) => { return Ok(()); }
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+			Err(err) => { return Err(format!("Failed to start {}: {}", terminal, err)); }
+		}
+	}
+	Err("No terminal found, set $TERMINAL".to_string())
+}
+
+#[cfg(windows)]
+pub fn spawnTerminal(dir: &Path, command: &[String]) -> Result<(), String> {
+	let keep: &[&str] = if command.is_empty() { &[] } else { &["/k"] };
+	process::Command::new("cmd").args(["/c", "start", "cmd"]).args(keep).args(command).current_dir(dir).spawn()
+	                            .map(|_| ()).map_err(|err| format!("Failed to start cmd: {}", err))
+}
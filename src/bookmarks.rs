@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::configHome;
+use crate::uri::{pathToUri, uriToPath};
+
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+	pub path: PathBuf,
+	pub label: String,
+}
+
+/// Shared with GTK file choosers and Nautilus
+pub fn gtkBookmarksFile() -> Option<PathBuf> {
+	Some(configHome()?.join("gtk-3.0").join("bookmarks"))
+}
+
+/// Every line is a uri optionally followed by a space and a label. Bookmarks of other places than local files are skipped.
+pub fn read() -> Vec<Bookmark> {
+	readLines().iter().filter_map(|l| parse(l)).collect()
+}
+
+/// All lines of the file as they are, including the bookmarks of network places this can't show
+fn readLines() -> Vec<String> {
+	gtkBookmarksFile().and_then(|f| fs::read_to_string(f).ok())
+	                  .map(|d| d.lines().filter(|l| !l.trim().is_empty()).map(str::to_string).collect())
+	                  .unwrap_or_default()
+}
+
+fn parse(line: &str) -> Option<Bookmark> {
+	let (uri, label) = match line.trim().split_once(' ') {
+		None => { (line.trim(), "") }
+		Some((uri, label)) => { (uri, label.trim()) }
+	};
+	let path = PathBuf::from(uriToPath(uri)?);
+	let label = if label.is_empty() {
+		path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path.to_string_lossy().into_owned())
+	} else {
+		label.to_string()
+	};
+	Some(Bookmark { path, label })
+}
+
+/// The line of the index-th bookmark read returns
+fn lineOf(lines: &[String], index: usize) -> Option<usize> {
+	lines.iter().enumerate().filter(|(_, l)| parse(l).is_some()).nth(index).map(|(i, _)| i)
+}
+
+fn writeLines(lines: &[String]) -> Result<(), String> {
+	let file = gtkBookmarksFile().ok_or("No config directory")?;
+	if let Some(dir) = file.parent() {
+		fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+	}
+	let data: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+	fs::write(&file, data).map_err(|err| format!("{}: {}", file.display(), err))
+}
+
+/// Labels that are just the folder name are not written, like GTK does
+fn formatLine(b: &Bookmark) -> String {
+	let mut line = pathToUri(&b.path);
+	if b.path.file_name().map(|n| n.to_string_lossy() != b.label).unwrap_or(true) {
+		line += " ";
+		line += &b.label;
+	}
+	line
+}
+
+pub fn add(path: PathBuf) -> Result<(), String> {
+	let mut lines = readLines();
+	if lines.iter().filter_map(|l| parse(l)).any(|b| b.path == path) {
+		return Ok(());
+	}
+	let label = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path.to_string_lossy().into_owned());
+	lines.push(formatLine(&Bookmark { path, label }));
+	writeLines(&lines)
+}
+
+pub fn remove(index: usize) -> Result<(), String> {
+	let mut lines = readLines();
+	match lineOf(&lines, index) {
+		None => { Ok(()) }
+		Some(line) => {
+			lines.remove(line);
+			writeLines(&lines)
+		}
+	}
+}
+
+/// Moves the bookmark at from so it ends up at index to. Other lines keep their place.
+pub fn reorder(from: usize, to: usize) -> Result<(), String> {
+	let mut lines = readLines();
+	let (fromLine, toLine) = match (lineOf(&lines, from), lineOf(&lines, to)) {
+		(Some(f), Some(t)) if f != t => { (f, t) }
+		_ => { return Ok(()); }
+	};
+	let line = lines.remove(fromLine);
+	lines.insert(toLine, line);
+	writeLines(&lines)
+}
@@ -0,0 +1,279 @@
+use std::{fs, io, process};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use regex::{NoExpand, Regex};
+
+use crate::datetime::{formatTime, localTime};
+use crate::fileops::{checkName, renameNoReplace};
+use crate::photo::{self, PhotoInfo};
+
+/// Wider counters only make absurdly long names
+const MAX_COUNTER_WIDTH: usize = 10;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Case {
+	Keep,
+	Lower,
+	Upper,
+	Title,
+}
+
+impl Case {
+	pub fn from(index: i32) -> Self {
+		match index {
+			1 => { Case::Lower }
+			2 => { Case::Upper }
+			3 => { Case::Title }
+			_ => { Case::Keep }
+		}
+	}
+	
+	fn apply(&self, text: &str) -> String {
+		match self {
+			Case::Keep => { text.to_string() }
+			Case::Lower => { text.to_lowercase() }
+			Case::Upper => { text.to_uppercase() }
+			Case::Title => {
+				let mut res = String::with_capacity(text.len());
+				let mut start = true;
+				for c in text.chars() {
+					if start { res.extend(c.to_uppercase()) } else { res.extend(c.to_lowercase()) }
+					start = !c.is_alphanumeric() && c != '\'';
+				}
+				res
+			}
+		}
+	}
+}
+
+/// How every name in the batch is turned into its new one
+pub struct Pattern {
+	pub find: String,
+	pub replace: String,
+	/// Find is a regex and replace can refer to its groups with $1 or ${name}
+	pub regex: bool,
+	/// The new name with tokens like {name}, {ext}, {n:3} or {date}
+	pub template: String,
+	pub counterStart: i64,
+	pub case: Case,
+}
+
+pub struct Row {
+	pub old: PathBuf,
+	pub new: PathBuf,
+	/// Empty if the rename can be done
+	pub problem: String,
+}
+
+/// The files being renamed. Metadata the tokens need is read once and kept while the dialog is open.
+pub struct Batch {
+	files: Vec<PathBuf>,
+	modified: Vec<Option<i64>>,
+	exif: Vec<Option<PhotoInfo>>,
+	exifRead: bool,
+}
+
+impl Batch {
+	pub fn new(mut files: Vec<PathBuf>) -> Self {
+		files.sort_by_key(|p| p.to_string_lossy().to_lowercase());
+		let modified = files.iter().map(|f| {
+			fs::metadata(f).and_then(|m| m.modified()).ok()
+			               .and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs() as i64)
+		}).collect();
+		Self { files, modified, exif: vec![], exifRead: false }
+	}
+	
+	/// Every file with its new name and what keeps it from being renamed. Fails if the pattern itself is broken.
+	pub fn preview(&mut self, pattern: &Pattern) -> Result<Vec<Row>, String> {
+		let find = if pattern.find.is_empty() {
+			None
+		} else if pattern.regex {
+			Some(Regex::new(&pattern.find).map_err(|err| err.to_string())?)
+		} else {
+			Some(Regex::new(&regex::escape(&pattern.find)).unwrap())
+		};
+		let tokens = parseTemplate(&pattern.template)?;
+		// Reading EXIF is slow, so only done once a template asks for it
+		if !self.exifRead && tokens.iter().any(|t| matches!(t, Token::Taken(_) | Token::Camera)) {
+			self.exif = self.files.iter().map(|f| photo::read(f)).collect();
+			self.exifRead = true;
+		}
+		
+		let mut rows = vec![];
+		for (i, old) in self.files.iter().enumerate() {
+			let name = old.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+			let replaced = match &find {
+				None => { name.clone() }
+				Some(re) if pattern.regex => { re.replace_all(&name, pattern.replace.as_str()).into_owned() }
+				Some(re) => { re.replace_all(&name, NoExpand(&pattern.replace)).into_owned() }
+			};
+			let (stem, ext) = match replaced.rfind('.') {
+				Some(i) if i > 0 => { (&replaced[..i], &replaced[i..]) }
+				_ => { (replaced.as_str(), "") }
+			};
+			
+			let mut newName = String::new();
+			for token in &tokens {
+				match token {
+					Token::Text(t) => { newName += t; }
+					Token::Name => { newName += stem; }
+					Token::Ext => { newName += ext; }
+					Token::Counter(width) => { newName += &format!("{:0width$}", pattern.counterStart + i as i64, width = *width); }
+					Token::Date(format) => {
+						if let Some(secs) = self.modified[i] {
+							newName += &formatTime(localTime(secs), format);
+						}
+					}
+					Token::Taken(format) => {
+						let taken = self.exif.get(i).and_then(|e| e.as_ref()).and_then(|e| e.taken)
+						                .or_else(|| self.modified[i].map(localTime));
+						if let Some(t) = taken {
+							newName += &formatTime(t, format);
+						}
+					}
+					Token::Camera => {
+						if let Some(e) = self.exif.get(i).and_then(|e| e.as_ref()) {
+							newName += &e.model;
+						}
+					}
+				}
+			}
+			let newName = pattern.case.apply(&newName);
+			
+			let problem = checkName(&newName).err().unwrap_or_default();
+			rows.push(Row { old: old.clone(), new: old.with_file_name(newName), problem });
+		}
+		
+		let olds: HashSet<String> = self.files.iter().map(|p| collisionKey(p)).collect();
+		let mut counts: HashMap<String, usize> = HashMap::new();
+		for r in &rows {
+			*counts.entry(collisionKey(&r.new)).or_default() += 1;
+		}
+		for r in &mut rows {
+			if !r.problem.is_empty() || r.new == r.old {
+				continue;
+			}
+			let name = r.new.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+			if counts[&collisionKey(&r.new)] > 1 {
+				r.problem = format!("{} is used more than once", name);
+			} else if !olds.contains(&collisionKey(&r.new)) && fs::symlink_metadata(&r.new).is_ok() {
+				r.problem = format!("{} already exists", name);
+			}
+		}
+		Ok(rows)
+	}
+}
+
+/// The filesystems Windows and macOS use by default don't tell names apart by case
+fn collisionKey(path: &Path) -> String {
+	if cfg!(any(windows, target_os = "macos")) { path.to_string_lossy().to_lowercase() } else { path.to_string_lossy().into_owned() }
+}
+
+enum Token {
+	Text(String),
+	Name,
+	Ext,
+	/// Padded to the width
+	Counter(usize),
+	/// Modification time in the format
+	Date(String),
+	/// When the photo was taken according to EXIF, the modification time otherwise
+	Taken(String),
+	Camera,
+}
+
+fn parseTemplate(template: &str) -> Result<Vec<Token>, String> {
+	let mut res = vec![];
+	let mut rest = template;
+	while let Some(start) = rest.find('{') {
+		if start > 0 {
+			res.push(Token::Text(rest[..start].to_string()));
+		}
+		let end = rest[start..].find('}').ok_or("A { is not closed")? + start;
+		let inner = &rest[start + 1..end];
+		let (name, arg) = match inner.split_once(':') {
+			None => { (inner, None) }
+			Some((n, a)) => { (n, Some(a)) }
+		};
+		res.push(match (name, arg) {
+			("name", None) => { Token::Name }
+			("ext", None) => { Token::Ext }
+			("n", None) => { Token::Counter(1) }
+			("n", Some(w)) => {
+				let width: usize = w.parse().map_err(|_| format!("{{n:{}}} needs a number of digits", w))?;
+				if width > MAX_COUNTER_WIDTH {
+					return Err(format!("{{n:{}}} is too wide, at most {} digits are allowed", w, MAX_COUNTER_WIDTH));
+				}
+				Token::Counter(width)
+			}
+			("date", f) => { Token::Date(f.unwrap_or("%Y-%m-%d").to_string()) }
+			("taken", f) => { Token::Taken(f.unwrap_or("%Y-%m-%d %H.%M.%S").to_string()) }
+			("camera", None) => { Token::Camera }
+			_ => { return Err(format!("Unknown token {{{}}}", inner)); }
+		});
+		rest = &rest[end + 1..];
+	}
+	if !rest.is_empty() {
+		res.push(Token::Text(rest.to_string()));
+	}
+	Ok(res)
+}
+
+/// Renames everything or, if something fails on the way, puts back what was already renamed.
+/// Every file first gets a temporary name so names can be swapped between files of the batch.
+pub fn apply(renames: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+	let renames: Vec<&(PathBuf, PathBuf)> = renames.iter().filter(|(old, new)| old != new).collect();
+	let temps: Vec<PathBuf> = renames.iter().enumerate()
+	                                 .map(|(i, (old, _))| old.with_file_name(format!(".rexplorer-rename-{}-{}", process::id(), i)))
+	                                 .collect();
+	
+	for i in 0..renames.len() {
+		if let Err(err) = fs::rename(&renames[i].0, &temps[i]) {
+			rollback(&renames, &temps, i, 0);
+			return Err(format!("Failed to rename {}: {}", renames[i].0.display(), err));
+		}
+	}
+	// Every old name is free now, so anything at a new name appeared after the preview and must not be replaced
+	for i in 0..renames.len() {
+		if let Err(err) = renameNoReplace(&temps[i], &renames[i].1) {
+			rollback(&renames, &temps, renames.len(), i);
+			if err.kind() == io::ErrorKind::AlreadyExists {
+				return Err(format!("{} already exists", renames[i].1.display()));
+			}
+			return Err(format!("Failed to rename {}: {}", renames[i].0.display(), err));
+		}
+	}
+	Ok(())
+}
+
+/// Undoes the first moved renames to temporary names and the first named ones to the new names
+fn rollback(renames: &[&(PathBuf, PathBuf)], temps: &[PathBuf], moved: usize, named: usize) {
+	for i in (0..named).rev() {
+		fs::rename(&renames[i].1, &temps[i]).map_err(|err| println!("Failed to undo rename of {}: {}", renames[i].1.display(), err)).ok();
+	}
+	for i in (0..moved).rev() {
+		renameNoReplace(&temps[i], &renames[i].0).map_err(|err| println!("Failed to undo rename of {}: {}", temps[i].display(), err)).ok();
+	}
+}
+
+/// Whether revert can put the names of an applied batch back, which it can as long as nothing was changed since
+pub fn checkRevert(renames: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+	let news: HashSet<String> = renames.iter().map(|(_, new)| collisionKey(new)).collect();
+	for (old, new) in renames {
+		if fs::symlink_metadata(new).is_err() {
+			return Err(format!("Can not undo, {} is gone", new.display()));
+		}
+		if !news.contains(&collisionKey(old)) && fs::symlink_metadata(old).is_ok() {
+			return Err(format!("Can not undo, {} exists again", old.display()));
+		}
+	}
+	Ok(())
+}
+
+/// Puts the names of an applied batch back. Like apply, all of them are renamed or none.
+pub fn revert(renames: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+	let back: Vec<(PathBuf, PathBuf)> = renames.iter().map(|(old, new)| (new.clone(), old.clone())).collect();
+	apply(&back)
+}
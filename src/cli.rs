@@ -0,0 +1,116 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: rexplorer [OPTIONS] [PATH]...
+
+Opens every PATH in its own window. A PATH that points to a file opens its folder with the file selected.
+
+Options:
+  --select FILE   Open the folder containing FILE and highlight it
+  --new-window    Always open a new window
+  --config DIR    Read and write configuration files in DIR
+  -h, --help      Print this message";
+
+pub struct Target {
+	pub dir: String,
+	pub select: Vec<String>,
+}
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug, Default, Clone)]
+pub struct Args {
+	pub paths: Vec<String>,
+	pub select: Vec<String>,
+	pub newWindow: bool,
+	pub configDir: Option<PathBuf>,
+	/// Shows the properties of the selected files, for ShowItemProperties over D-Bus
+	#[serde(default)]
+	pub properties: bool,
+}
+
+pub enum ParseResult {
+	Run(Args),
+	Help,
+}
+
+impl Args {
+	pub fn parse<I: Iterator<Item=String>>(mut args: I) -> Result<ParseResult, String> {
+		let mut res = Args::default();
+		let mut onlyPaths = false;
+		
+		while let Some(arg) = args.next() {
+			if onlyPaths || !arg.starts_with('-') || arg == "-" {
+				res.paths.push(absolute(&arg));
+				continue;
+			}
+			
+			let (name, inlineVal) = match arg.split_once('=') {
+				None => { (arg.as_str(), None) }
+				Some((n, v)) => { (n, Some(v.to_string())) }
+			};
+			
+			let mut value = |name: &str| {
+				inlineVal.clone()
+				         .or_else(|| args.next())
+				         .ok_or_else(|| format!("Missing value for {name}"))
+			};
+			
+			match name {
+				"--" => { onlyPaths = true; }
+				"-h" | "--help" => { return Ok(ParseResult::Help); }
+				"--new-window" => { res.newWindow = true; }
+				"--select" => { res.select.push(absolute(&value(name)?)); }
+				"--config" => { res.configDir = Some(PathBuf::from(absolute(&value(name)?))); }
+				_ => { return Err(format!("Unknown option {arg}")); }
+			}
+		}
+		
+		Ok(ParseResult::Run(res))
+	}
+	
+	pub fn usage() -> &'static str { USAGE }
+	
+	pub fn targets(&self) -> Vec<Target> {
+		let mut targets = vec![];
+		
+		for path in &self.paths {
+			if Path::new(path).is_file() {
+				addReveal(&mut targets, path);
+			} else {
+				targets.push(Target { dir: path.clone(), select: vec![] });
+			}
+		}
+		for file in &self.select {
+			addReveal(&mut targets, file);
+		}
+		
+		targets
+	}
+}
+
+/// Files that live in the same folder are selected together in one window
+fn addReveal(targets: &mut Vec<Target>, file: &str) {
+	let path = Path::new(file);
+	let dir = path.parent().and_then(|p| p.to_str()).unwrap_or(file);
+	let name = match path.file_name().and_then(|n| n.to_str()) {
+		None => { return; }
+		Some(n) => { n.to_string() }
+	};
+	
+	match targets.iter_mut().find(|t| t.dir == dir) {
+		None => { targets.push(Target { dir: dir.to_string(), select: vec![name] }); }
+		Some(t) => { t.select.push(name); }
+	}
+}
+
+/// Paths are resolved against the working directory of the launching process so they stay valid when handed to another window.
+fn absolute(path: &str) -> String {
+	let p = Path::new(path);
+	if p.is_absolute() {
+		return path.to_string();
+	}
+	env::current_dir()
+		.map(|cwd| cwd.join(p))
+		.ok()
+		.and_then(|p| p.to_str().map(|s| s.to_string()))
+		.unwrap_or_else(|| path.to_string())
+}
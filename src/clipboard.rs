@@ -0,0 +1,442 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use lazy_static::lazy_static;
+
+use crate::uri::{pathToUri, uriToPath};
+
+lazy_static! {
+	/// What was last copied in this process, used when there is no system clipboard to talk to
+	static ref LOCAL: Mutex<Option<Content>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone)]
+pub enum Content {
+	Files { paths: Vec<PathBuf>, cut: bool },
+	/// Encoded image data with its mime type
+	Image { mime: String, data: Vec<u8> },
+	/// Only ever copied, not pasted as files
+	Text(String),
+}
+
+/// Puts the files on the clipboard the way GTK and Qt file managers do
+pub fn setFiles(paths: Vec<PathBuf>, cut: bool) {
+	let content = Content::Files { paths, cut };
+	*LOCAL.lock().unwrap() = Some(content.clone());
+	system::set(content).map_err(|err| {
+		println!("System clipboard not available, only this process can paste: {}", err);
+	}).ok();
+}
+
+/// Plain text for other programs, like paths for a terminal
+pub fn setText(text: String) {
+	let content = Content::Text(text);
+	*LOCAL.lock().unwrap() = Some(content.clone());
+	system::set(content).map_err(|err| {
+		println!("System clipboard not available: {}", err);
+	}).ok();
+}
+
+/// What can be pasted as files, None if the clipboard holds nothing useful
+pub fn get() -> Result<Option<Content>, String> {
+	match system::get() {
+		Err(err) => {
+			println!("System clipboard not available, using the local one: {}", err);
+			Ok(LOCAL.lock().unwrap().clone().filter(|c| !matches!(c, Content::Text(_))))
+		}
+		Ok(content) => { Ok(content) }
+	}
+}
+
+/// Reads the clipboard on another thread, the owner can take its time to answer
+pub fn getAsync() -> Receiver<Result<Option<Content>, String>> {
+	let (send, receiver) = channel();
+	thread::Builder::new().name("Clipboard read".into()).spawn(move || {
+		let _ = send.send(get());
+	}).map_err(|err| println!("Failed to read the clipboard: {}", err)).ok();
+	receiver
+}
+
+/// Cut files are only pasted once, so the clipboard is emptied after they moved. Something copied since then is kept.
+pub fn clearCut(moved: &[PathBuf]) {
+	let mut local = LOCAL.lock().unwrap();
+	match local.as_ref() {
+		Some(Content::Files { paths, cut: true }) if paths == moved => {}
+		_ => { return; }
+	}
+	*local = None;
+	system::clear();
+}
+
+/// x-special/gnome-copied-files, the operation followed by one uri per line
+fn gnomeCopiedFiles(paths: &[PathBuf], cut: bool) -> String {
+	let mut res = if cut { "cut".to_string() } else { "copy".to_string() };
+	for p in paths {
+		res += "\n";
+		res += &pathToUri(p);
+	}
+	res
+}
+
+/// text/uri-list from RFC 2483, lines end with \r\n
+fn uriList(paths: &[PathBuf]) -> String {
+	paths.iter().map(|p| pathToUri(p) + "\r\n").collect()
+}
+
+fn parseGnomeCopiedFiles(text: &str) -> Option<Content> {
+	let mut lines = text.lines();
+	let cut = match lines.next()?.trim() {
+		"cut" => { true }
+		"copy" => { false }
+		_ => { return None; }
+	};
+	let paths = parseUris(lines);
+	if paths.is_empty() { None } else { Some(Content::Files { paths, cut }) }
+}
+
+fn parseUris<'a, I: Iterator<Item=&'a str>>(lines: I) -> Vec<PathBuf> {
+	lines.map(|l| l.trim())
+	     .filter(|l| !l.is_empty() && !l.starts_with('#'))
+	     .filter_map(|l| uriToPath(l).map(PathBuf::from))
+	     .collect()
+}
+
+/// Writes an image from the clipboard into dir as png
+pub fn saveImage(mime: &str, data: &[u8], dir: &Path) -> Result<PathBuf, String> {
+	let target = crate::fileops::uniqueName(&dir.join("Pasted image.png"));
+	if mime == "image/png" {
+		std::fs::write(&target, data).map_err(|err| format!("{}: {}", target.display(), err))?;
+	} else {
+		let img = image::load_from_memory(data).map_err(|err| format!("Failed to read {} from the clipboard: {}", mime, err))?;
+		img.save_with_format(&target, image::ImageFormat::Png).map_err(|err| format!("{}: {}", target.display(), err))?;
+	}
+	Ok(target)
+}
+
+/// Talks to the X11 clipboard, Wayland sessions see it through XWayland
+#[cfg(target_os = "linux")]
+mod system {
+	use std::sync::{Arc, Mutex};
+	use std::thread;
+	use std::time::{Duration, Instant};
+	
+	use lazy_static::lazy_static;
+	use x11rb::connection::{Connection, RequestConnection};
+	use x11rb::protocol::Event;
+	use x11rb::protocol::xproto::{Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, CreateWindowAux, EventMask, PropMode, Property, SELECTION_NOTIFY_EVENT, SelectionNotifyEvent, Window, WindowClass};
+	use x11rb::rust_connection::RustConnection;
+	use x11rb::wrapper::ConnectionExt as _;
+	use x11rb::{COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE};
+	
+	use super::{Content, gnomeCopiedFiles, parseGnomeCopiedFiles, parseUris, uriList};
+	
+	const TIMEOUT: Duration = Duration::from_millis(700);
+	/// Preferred first
+	const IMAGE_TYPES: [&str; 5] = ["image/png", "image/jpeg", "image/bmp", "image/gif", "image/webp"];
+	
+	lazy_static! {
+		static ref OWNER: Mutex<Option<Arc<Owner>>> = Mutex::new(None);
+	}
+	
+	struct XWindow {
+		conn: RustConnection,
+		window: Window,
+	}
+	
+	impl XWindow {
+		fn new() -> Result<Self, String> {
+			let (conn, screen) = x11rb::connect(None).map_err(|err| err.to_string())?;
+			let window = conn.generate_id().map_err(|err| err.to_string())?;
+			let root = &conn.setup().roots[screen];
+			conn.create_window(COPY_DEPTH_FROM_PARENT, window, root.root, 0, 0, 1, 1, 0, WindowClass::INPUT_OUTPUT, root.root_visual,
+			                   &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE))
+			    .map_err(|err| err.to_string())?;
+			Ok(Self { conn, window })
+		}
+		
+		fn atom(&self, name: &str) -> Result<Atom, String> {
+			self.conn.intern_atom(false, name.as_bytes()).map_err(|err| err.to_string())?
+			         .reply().map_err(|err| err.to_string()).map(|r| r.atom)
+		}
+	}
+	
+	/// Keeps a hidden window around that answers requests for what was copied
+	struct Owner {
+		x: XWindow,
+		clipboard: Atom,
+		targets: Atom,
+		incr: Atom,
+		offered: Mutex<Vec<(Atom, Vec<u8>)>>,
+	}
+	
+	/// Data too large for a single request, sent in chunks each time the requestor deletes the property
+	struct Transfer {
+		requestor: Window,
+		property: Atom,
+		target: Atom,
+		data: Vec<u8>,
+		sent: usize,
+	}
+	
+	fn owner() -> Result<Arc<Owner>, String> {
+		let mut owner = OWNER.lock().unwrap();
+		if let Some(o) = owner.as_ref() {
+			return Ok(o.clone());
+		}
+		
+		let x = XWindow::new()?;
+		let o = Arc::new(Owner { clipboard: x.atom("CLIPBOARD")?, targets: x.atom("TARGETS")?, incr: x.atom("INCR")?, x, offered: Mutex::new(vec![]) });
+		let tOwner = o.clone();
+		thread::Builder::new().name("Clipboard".into()).spawn(move || {
+			serve(&tOwner);
+		}).map_err(|err| err.to_string())?;
+		*owner = Some(o.clone());
+		Ok(o)
+	}
+	
+	fn serve(owner: &Owner) {
+		let chunkSize = owner.x.conn.maximum_request_bytes() / 4;
+		let mut transfers: Vec<Transfer> = vec![];
+		loop {
+			let event = match owner.x.conn.wait_for_event() {
+				Ok(e) => { e }
+				Err(err) => {
+					println!("Clipboard connection lost: {}", err);
+					*OWNER.lock().unwrap() = None;
+					return;
+				}
+			};
+			match event {
+				Event::SelectionClear(_) => {
+					owner.offered.lock().unwrap().clear();
+				}
+				Event::SelectionRequest(req) => {
+					let offered = owner.offered.lock().unwrap();
+					// Old clients send no property and expect the target to be used
+					let property = if req.property == NONE { req.target } else { req.property };
+					let answered = if req.target == owner.targets && !offered.is_empty() {
+						let mut atoms: Vec<Atom> = offered.iter().map(|(a, _)| *a).collect();
+						atoms.push(owner.targets);
+						owner.x.conn.change_property32(PropMode::REPLACE, req.requestor, property, AtomEnum::ATOM, &atoms).is_ok()
+					} else {
+						match offered.iter().find(|(a, _)| *a == req.target) {
+							None => { false }
+							Some((target, data)) if data.len() > chunkSize => {
+								// Announces the size, the chunks follow as the requestor deletes the property
+								let listen = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+								let started = owner.x.conn.change_window_attributes(req.requestor, &listen).is_ok()
+								              && owner.x.conn.change_property32(PropMode::REPLACE, req.requestor, property, owner.incr, &[data.len() as u32]).is_ok();
+								if started {
+									// A requestor that gave up on an earlier transfer to the same property starts over
+									transfers.retain(|t| t.requestor != req.requestor || t.property != property);
+									transfers.push(Transfer { requestor: req.requestor, property, target: *target, data: data.clone(), sent: 0 });
+								}
+								started
+							}
+							Some((target, data)) => {
+								owner.x.conn.change_property8(PropMode::REPLACE, req.requestor, property, *target, data).is_ok()
+							}
+						}
+					};
+					
+					let notify = SelectionNotifyEvent {
+						response_type: SELECTION_NOTIFY_EVENT,
+						sequence: 0,
+						time: req.time,
+						requestor: req.requestor,
+						selection: req.selection,
+						target: req.target,
+						property: if answered { property } else { NONE },
+					};
+					owner.x.conn.send_event(false, req.requestor, EventMask::NO_EVENT, notify).ok();
+					owner.x.conn.flush().ok();
+				}
+				Event::PropertyNotify(p) if p.state == Property::DELETE => {
+					let i = match transfers.iter().position(|t| t.requestor == p.window && t.property == p.atom) {
+						None => { continue; }
+						Some(i) => { i }
+					};
+					let t = &mut transfers[i];
+					let end = (t.sent + chunkSize).min(t.data.len());
+					// The empty chunk at the end tells the requestor that the transfer is complete
+					let ok = owner.x.conn.change_property8(PropMode::REPLACE, t.requestor, t.property, t.target, &t.data[t.sent..end]).is_ok();
+					let finished = !ok || t.sent == end;
+					t.sent = end;
+					if finished {
+						let t = transfers.remove(i);
+						owner.x.conn.change_window_attributes(t.requestor, &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT)).ok();
+					}
+					owner.x.conn.flush().ok();
+				}
+				_ => {}
+			}
+		}
+	}
+	
+	pub fn set(content: Content) -> Result<(), String> {
+		let owner = owner()?;
+		let (paths, cut) = match content {
+			Content::Files { paths, cut } => { (paths, cut) }
+			Content::Image { .. } => { return Err("Only files can be copied".to_string()); }
+			Content::Text(text) => {
+				let offered = vec![
+					(owner.x.atom("UTF8_STRING")?, text.clone().into_bytes()),
+					(owner.x.atom("text/plain;charset=utf-8")?, text.into_bytes()),
+				];
+				return take(&owner, offered);
+			}
+		};
+		let text = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect::<Vec<_>>().join("\n");
+		let mut offered = vec![
+			(owner.x.atom("x-special/gnome-copied-files")?, gnomeCopiedFiles(&paths, cut).into_bytes()),
+			(owner.x.atom("text/uri-list")?, uriList(&paths).into_bytes()),
+			// Terminals and editors get the plain paths
+			(owner.x.atom("UTF8_STRING")?, text.clone().into_bytes()),
+			(owner.x.atom("text/plain;charset=utf-8")?, text.into_bytes()),
+		];
+		if cut {
+			offered.push((owner.x.atom("application/x-kde-cutselection")?, b"1".to_vec()));
+		}
+		take(&owner, offered)
+	}
+	
+	/// Becomes the owner of the clipboard with the new data
+	fn take(owner: &Owner, offered: Vec<(Atom, Vec<u8>)>) -> Result<(), String> {
+		*owner.offered.lock().unwrap() = offered;
+		
+		owner.x.conn.set_selection_owner(owner.x.window, owner.clipboard, CURRENT_TIME).map_err(|err| err.to_string())?;
+		owner.x.conn.flush().map_err(|err| err.to_string())?;
+		Ok(())
+	}
+	
+	pub fn clear() {
+		let owner = match OWNER.lock().unwrap().clone() {
+			None => { return; }
+			Some(o) => { o }
+		};
+		let mut offered = owner.offered.lock().unwrap();
+		if offered.is_empty() {
+			return;
+		}
+		offered.clear();
+		owner.x.conn.set_selection_owner(NONE, owner.clipboard, CURRENT_TIME).ok();
+		owner.x.conn.flush().ok();
+	}
+	
+	pub fn get() -> Result<Option<Content>, String> {
+		let x = XWindow::new()?;
+		let clipboard = x.atom("CLIPBOARD")?;
+		let property = x.atom("REXPLORER_CLIPBOARD")?;
+		
+		let targets = match read(&x, clipboard, x.atom("TARGETS")?, property)? {
+			None => { return Ok(None); }
+			Some(data) => { data }
+		};
+		let mut names = vec![];
+		for chunk in targets.chunks_exact(4) {
+			let atom = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+			if let Ok(reply) = x.conn.get_atom_name(atom).map_err(|err| err.to_string())?.reply() {
+				names.push(String::from_utf8_lossy(&reply.name).into_owned());
+			}
+		}
+		
+		if names.iter().any(|n| n == "x-special/gnome-copied-files") {
+			if let Some(data) = read(&x, clipboard, x.atom("x-special/gnome-copied-files")?, property)? {
+				return Ok(parseGnomeCopiedFiles(&String::from_utf8_lossy(&data)));
+			}
+		}
+		if names.iter().any(|n| n == "text/uri-list") {
+			if let Some(data) = read(&x, clipboard, x.atom("text/uri-list")?, property)? {
+				let paths = parseUris(String::from_utf8_lossy(&data).lines());
+				if paths.is_empty() {
+					return Ok(None);
+				}
+				let cut = names.iter().any(|n| n == "application/x-kde-cutselection")
+				          && read(&x, clipboard, x.atom("application/x-kde-cutselection")?, property)?.as_deref() == Some(b"1");
+				return Ok(Some(Content::Files { paths, cut }));
+			}
+		}
+		for mime in IMAGE_TYPES {
+			if names.iter().any(|n| n == mime) {
+				if let Some(data) = read(&x, clipboard, x.atom(mime)?, property)? {
+					return Ok(Some(Content::Image { mime: mime.to_string(), data }));
+				}
+			}
+		}
+		Ok(None)
+	}
+	
+	/// Asks the owner to convert the selection and collects the result, also when it arrives in INCR chunks
+	fn read(x: &XWindow, selection: Atom, target: Atom, property: Atom) -> Result<Option<Vec<u8>>, String> {
+		let incr = x.atom("INCR")?;
+		x.conn.convert_selection(x.window, selection, target, property, CURRENT_TIME).map_err(|err| err.to_string())?;
+		x.conn.flush().map_err(|err| err.to_string())?;
+		
+		let converted = waitFor(x, |e| match e {
+			Event::SelectionNotify(n) => { Some(n.property != NONE) }
+			_ => { None }
+		})?;
+		if !converted {
+			return Ok(None);
+		}
+		
+		let reply = x.conn.get_property(true, x.window, property, AtomEnum::ANY, 0, u32::MAX / 4).map_err(|err| err.to_string())?
+		             .reply().map_err(|err| err.to_string())?;
+		if reply.type_ != incr {
+			return Ok(Some(reply.value));
+		}
+		
+		// The owner sends chunks every time we delete the property, an empty one ends the transfer
+		let mut data = vec![];
+		x.conn.flush().map_err(|err| err.to_string())?;
+		loop {
+			waitFor(x, |e| match e {
+				Event::PropertyNotify(p) if p.atom == property && p.state == Property::NEW_VALUE => { Some(()) }
+				_ => { None }
+			})?;
+			let chunk = x.conn.get_property(true, x.window, property, AtomEnum::ANY, 0, u32::MAX / 4).map_err(|err| err.to_string())?
+			             .reply().map_err(|err| err.to_string())?;
+			x.conn.flush().map_err(|err| err.to_string())?;
+			if chunk.value.is_empty() {
+				return Ok(Some(data));
+			}
+			data.extend_from_slice(&chunk.value);
+		}
+	}
+	
+	fn waitFor<R, F: Fn(&Event) -> Option<R>>(x: &XWindow, accept: F) -> Result<R, String> {
+		let start = Instant::now();
+		loop {
+			match x.conn.poll_for_event().map_err(|err| err.to_string())? {
+				Some(e) => {
+					if let Some(res) = accept(&e) {
+						return Ok(res);
+					}
+				}
+				None => {
+					if start.elapsed() > TIMEOUT {
+						return Err("The clipboard owner did not answer".to_string());
+					}
+					thread::sleep(Duration::from_millis(5));
+				}
+			}
+		}
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+mod system {
+	use super::Content;
+	
+	pub fn set(_content: Content) -> Result<(), String> {
+		Ok(())
+	}
+	
+	pub fn clear() {}
+	
+	pub fn get() -> Result<Option<Content>, String> {
+		Err("Not supported on this platform".to_string())
+	}
+}
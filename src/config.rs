@@ -1,10 +1,89 @@
-use std::{fs, thread};
-use std::sync::{Arc, Mutex};
+use std::{env, fs, thread};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::sleep;
 use std::time::Duration;
 
+use lazy_static::lazy_static;
 use slint::Window;
 
+lazy_static! {
+	static ref CONFIG_DIR: RwLock<PathBuf> = RwLock::new(PathBuf::from("."));
+	static ref SETTINGS: RwLock<Option<Settings>> = RwLock::new(None);
+}
+
+pub fn setConfigDir(dir: PathBuf) {
+	if let Err(err) = fs::create_dir_all(&dir) {
+		println!("Failed to create config directory {}: {}", dir.display(), err);
+	}
+	*CONFIG_DIR.write().unwrap() = dir;
+}
+
+pub fn configFile(name: &str) -> PathBuf {
+	CONFIG_DIR.read().unwrap().join(name)
+}
+
+/// Data that can be rebuilt at any time goes to $XDG_CACHE_HOME/rexplorer (%LOCALAPPDATA%\rexplorer on windows)
+pub fn cacheFile(name: &str) -> PathBuf {
+	let base = if cfg!(windows) {
+		env::var_os("LOCALAPPDATA").map(PathBuf::from)
+	} else {
+		env::var_os("XDG_CACHE_HOME").map(PathBuf::from).filter(|p| p.is_absolute())
+		                                .or_else(|| home::home_dir().map(|h| h.join(".cache")))
+	};
+	let dir = base.unwrap_or_else(env::temp_dir).join("rexplorer");
+	if let Err(err) = fs::create_dir_all(&dir) {
+		println!("Failed to create cache directory {}: {}", dir.display(), err);
+	}
+	dir.join(name)
+}
+
+/// Data that should survive restarts but is not configuration goes to $XDG_DATA_HOME/rexplorer (%APPDATA%\rexplorer on windows)
+pub fn dataFile(name: &str) -> PathBuf {
+	let base = if cfg!(windows) {
+		env::var_os("APPDATA").map(PathBuf::from)
+	} else {
+		dataHome()
+	};
+	let dir = base.unwrap_or_else(env::temp_dir).join("rexplorer");
+	if let Err(err) = fs::create_dir_all(&dir) {
+		println!("Failed to create data directory {}: {}", dir.display(), err);
+	}
+	dir.join(name)
+}
+
+/// $XDG_DATA_HOME or ~/.local/share
+pub fn dataHome() -> Option<PathBuf> {
+	env::var_os("XDG_DATA_HOME").map(PathBuf::from).filter(|p| p.is_absolute())
+	                            .or_else(|| home::home_dir().map(|h| h.join(".local").join("share")))
+}
+
+/// The data home followed by $XDG_DATA_DIRS, most important first
+pub fn dataDirs() -> Vec<PathBuf> {
+	let mut dirs: Vec<PathBuf> = dataHome().into_iter().collect();
+	dirs.extend(xdgList("XDG_DATA_DIRS", "/usr/local/share:/usr/share"));
+	dirs
+}
+
+/// $XDG_CONFIG_HOME or ~/.config
+pub fn configHome() -> Option<PathBuf> {
+	env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|p| p.is_absolute())
+	                              .or_else(|| home::home_dir().map(|h| h.join(".config")))
+}
+
+/// The config home followed by $XDG_CONFIG_DIRS, most important first
+pub fn configDirs() -> Vec<PathBuf> {
+	let mut dirs: Vec<PathBuf> = configHome().into_iter().collect();
+	dirs.extend(xdgList("XDG_CONFIG_DIRS", "/etc/xdg"));
+	dirs
+}
+
+/// Relative paths are invalid and ignored
+fn xdgList(key: &str, default: &str) -> Vec<PathBuf> {
+	let value = env::var(key).ok().filter(|v| !v.is_empty()).unwrap_or_else(|| default.to_string());
+	value.split(':').map(PathBuf::from).filter(|p| p.is_absolute()).collect()
+}
+
 #[derive(Debug, Clone)]
 pub struct WindowInfo {
 	pub winBox: WindowBox,
@@ -60,7 +139,7 @@ impl PartialEq for WindowBox {
 }
 
 pub fn readWindowBox() -> Option<WindowBox> {
-	fs::read_to_string("./WindowState.json")
+	fs::read_to_string(configFile("WindowState.json"))
 		.map_err(|err| {
 			println!("Config failed to read: {}", err);
 		})
@@ -76,7 +155,7 @@ fn validateData(s: &WindowBox) -> bool {
 }
 
 pub fn writeWindowBox(wbox: &WindowBox) {
-	fs::write("./WindowState.json", serde_json::to_string_pretty(wbox).unwrap()).map_err(|err| {
+	fs::write(configFile("WindowState.json"), serde_json::to_string_pretty(wbox).unwrap()).map_err(|err| {
 		println!("Failed to save config: {}", err);
 	}).ok();
 }
@@ -110,3 +189,59 @@ pub fn watchState(window: Arc<Mutex<WindowInfo>>, orgState: Option<WindowBox>) {
 		}
 	});
 }
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum IgnoreMode {
+	#[default]
+	Show,
+	Dim,
+	Hide,
+}
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+	pub showHidden: bool,
+	/// What to do with entries matched by .gitignore/.ignore files
+	pub ignoreMode: IgnoreMode,
+	/// Globs matched against the file name and the full path. Matching entries are treated like hidden files.
+	pub excludePatterns: Vec<String>,
+	pub hideSidebar: bool,
+	pub previewPane: bool,
+	/// Opens images with the system viewer instead of the built-in one
+	pub externalImageViewer: bool,
+	/// Keeps an index of everything below indexRoots so name searches there don't have to walk the disk
+	pub indexEnabled: bool,
+	/// The home directory when empty
+	pub indexRoots: Vec<PathBuf>,
+	/// Globs matched like excludePatterns, matching entries and everything below them are not indexed
+	pub indexExcludes: Vec<String>,
+	/// Rough limit for the memory the index may use, 0 means 256. Indexing stops at the limit and searches fall back to walking the disk.
+	pub indexMaxMemoryMB: u64,
+	/// Files offered by "New file" besides the ones in the XDG templates directory
+	pub fileTemplates: Vec<PathBuf>,
+}
+
+/// Settings are read once and shared by all windows
+pub fn settings() -> Settings {
+	if let Some(s) = SETTINGS.read().unwrap().as_ref() {
+		return s.clone();
+	}
+	let s = fs::read_to_string(configFile("Settings.json"))
+		.ok()
+		.and_then(|data| serde_json::from_str(&data).map_err(|err| {
+			println!("Settings malformed: {}", err);
+		}).ok())
+		.unwrap_or_default();
+	*SETTINGS.write().unwrap() = Some(s);
+	settings()
+}
+
+pub fn updateSettings<F: FnOnce(&mut Settings)>(change: F) {
+	let mut s = settings();
+	change(&mut s);
+	fs::write(configFile("Settings.json"), serde_json::to_string_pretty(&s).unwrap()).map_err(|err| {
+		println!("Failed to save settings: {}", err);
+	}).ok();
+	*SETTINGS.write().unwrap() = Some(s);
+}
@@ -0,0 +1,49 @@
+/// Year, month, day, hour, minute, second
+#[derive(Debug, Clone, Copy)]
+pub struct Civil(pub [i64; 6]);
+
+/// %Y %m %d %H %M %S, everything else is kept as is
+pub fn formatTime(time: Civil, format: &str) -> String {
+	let [y, mo, d, h, mi, s] = time.0;
+	format.replace("%Y", &format!("{:04}", y))
+	      .replace("%m", &format!("{:02}", mo))
+	      .replace("%d", &format!("{:02}", d))
+	      .replace("%H", &format!("{:02}", h))
+	      .replace("%M", &format!("{:02}", mi))
+	      .replace("%S", &format!("{:02}", s))
+}
+
+#[cfg(unix)]
+pub fn localTime(secs: i64) -> Civil {
+	let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+	let t = secs as libc::time_t;
+	if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+		return utcTime(secs);
+	}
+	Civil([tm.tm_year as i64 + 1900, tm.tm_mon as i64 + 1, tm.tm_mday as i64, tm.tm_hour as i64, tm.tm_min as i64, tm.tm_sec as i64])
+}
+
+#[cfg(not(unix))]
+pub fn localTime(secs: i64) -> Civil {
+	utcTime(secs)
+}
+
+pub fn utcTime(secs: i64) -> Civil {
+	let days = secs.div_euclid(86400);
+	let rem = secs.rem_euclid(86400);
+	let (y, m, d) = civilFromDays(days);
+	Civil([y, m, d, rem / 3600, rem / 60 % 60, rem % 60])
+}
+
+/// The inverse of search::daysFromCivil
+fn civilFromDays(days: i64) -> (i64, i64, i64) {
+	let z = days + 719468;
+	let era = if z >= 0 { z } else { z - 146096 } / 146097;
+	let doe = z - era * 146097;
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let d = doy - (153 * mp + 2) / 5 + 1;
+	let m = if mp < 10 { mp + 3 } else { mp - 9 };
+	(yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m, d)
+}
@@ -0,0 +1,140 @@
+use std::sync::mpsc::Sender;
+
+use zbus::blocking::{Connection, ConnectionBuilder};
+use zbus::dbus_interface;
+
+use crate::cli::Args;
+use crate::uri::uriToPath;
+
+const NAME: &str = "org.freedesktop.FileManager1";
+const PATH: &str = "/org/freedesktop/FileManager1";
+
+/// https://www.freedesktop.org/wiki/Specifications/file-manager-interface/
+struct FileManager1 {
+	send: Sender<Args>,
+}
+
+#[dbus_interface(name = "org.freedesktop.FileManager1")]
+impl FileManager1 {
+	#[dbus_interface(name = "ShowFolders")]
+	fn showFolders(&self, uris: Vec<String>, _startupId: String) {
+		self.forward(Args { paths: uriPaths(&uris), ..Default::default() });
+	}
+	
+	#[dbus_interface(name = "ShowItems")]
+	fn showItems(&self, uris: Vec<String>, _startupId: String) {
+		self.forward(Args { select: uriPaths(&uris), ..Default::default() });
+	}
+	
+	#[dbus_interface(name = "ShowItemProperties")]
+	fn showItemProperties(&self, uris: Vec<String>, _startupId: String) {
+		self.forward(Args { select: uriPaths(&uris), properties: true, ..Default::default() });
+	}
+}
+
+impl FileManager1 {
+	fn forward(&self, args: Args) {
+		if args.paths.is_empty() && args.select.is_empty() { return; }
+		let _ = self.send.send(args);
+	}
+}
+
+/// Registers the interface on the session bus (or whatever DBUS_SESSION_BUS_ADDRESS points to). The connection has to be kept alive for as long as the service should be reachable.
+pub fn serve(send: Sender<Args>) -> Result<Connection, String> {
+	serveOn(ConnectionBuilder::session(), send)
+}
+
+fn serveOn(builder: zbus::Result<ConnectionBuilder>, send: Sender<Args>) -> Result<Connection, String> {
+	builder
+		.and_then(|b| b.name(NAME))
+		.and_then(|b| b.serve_at(PATH, FileManager1 { send }))
+		.and_then(|b| b.build())
+		.map_err(|err| format!("Failed to register {NAME}: {err}"))
+}
+
+fn uriPaths(uris: &[String]) -> Vec<String> {
+	uris.iter().filter_map(|uri| {
+		let path = uriToPath(uri);
+		if path.is_none() {
+			println!("Unsupported uri {}", uri);
+		}
+		path
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{BufRead, BufReader};
+	use std::process::{Child, Command, Stdio};
+	use std::sync::mpsc::{channel, Receiver};
+	use std::time::Duration;
+	
+	use super::*;
+	
+	/// A bus of its own, so the test neither needs nor disturbs a desktop session
+	struct PrivateBus {
+		daemon: Child,
+		address: String,
+	}
+	
+	impl PrivateBus {
+		/// None when dbus-daemon is not installed
+		fn start() -> Option<Self> {
+			let mut daemon = Command::new("dbus-daemon")
+				.args(["--session", "--nofork", "--print-address"])
+				.stdout(Stdio::piped())
+				.spawn().ok()?;
+			let mut address = String::new();
+			BufReader::new(daemon.stdout.as_mut()?).read_line(&mut address).ok()?;
+			Some(Self { daemon, address: address.trim().to_string() })
+		}
+		
+		fn connect(&self) -> Connection {
+			ConnectionBuilder::address(self.address.as_str()).and_then(|b| b.build()).unwrap()
+		}
+	}
+	
+	impl Drop for PrivateBus {
+		fn drop(&mut self) {
+			let _ = self.daemon.kill();
+			let _ = self.daemon.wait();
+		}
+	}
+	
+	fn call(client: &Connection, method: &str, uris: &[&str], requests: &Receiver<Args>) -> Args {
+		client.call_method(Some(NAME), PATH, Some(NAME), method, &(uris, "")).unwrap();
+		requests.recv_timeout(Duration::from_secs(5)).unwrap()
+	}
+	
+	#[test]
+	fn dispatchesFileManagerMethods() {
+		let bus = match PrivateBus::start() {
+			Some(b) => { b }
+			None => {
+				println!("dbus-daemon is not installed, skipping");
+				return;
+			}
+		};
+		let (send, requests) = channel();
+		let _service = serveOn(ConnectionBuilder::address(bus.address.as_str()), send).unwrap();
+		let client = bus.connect();
+		
+		let args = call(&client, "ShowFolders", &["file:///home/user/My%20Documents", "file:///tmp"], &requests);
+		assert_eq!(args.paths, ["/home/user/My Documents", "/tmp"]);
+		assert!(args.select.is_empty());
+		assert!(!args.properties);
+		
+		let args = call(&client, "ShowItems", &["file:///tmp/a.txt"], &requests);
+		assert!(args.paths.is_empty());
+		assert_eq!(args.select, ["/tmp/a.txt"]);
+		assert!(!args.properties);
+		
+		let args = call(&client, "ShowItemProperties", &["file:///tmp/a.txt", "https://example.com/b.txt"], &requests);
+		assert_eq!(args.select, ["/tmp/a.txt"]);
+		assert!(args.properties);
+		
+		// Nothing to show, nothing is forwarded
+		client.call_method(Some(NAME), PATH, Some(NAME), "ShowFolders", &(&["https://example.com/"][..], "")).unwrap();
+		assert!(requests.recv_timeout(Duration::from_millis(200)).is_err());
+	}
+}
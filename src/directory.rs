@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, Sender};
+
+use slint::{Model, ModelRc, SharedString, VecModel};
+
+use crate::icon::FileLoaderAction;
+use crate::matching::NameMatcher;
+use crate::search::SearchHandle;
+use crate::{UIDirectoryInfo, UIFile};
+
+/// Holds every entry of the open directory. The UI model only contains the entries that pass the filter.
+pub struct DirectoryReader {
+	pub fullPath: String,
+	status: String,
+	files: Vec<UIFile>,
+	pub pathIndex: HashMap<String, usize>,
+	/// Indices into files of the entries that are in the model, in ascending order
+	shown: Vec<usize>,
+	model: Rc<VecModel<UIFile>>,
+	filter: NameMatcher,
+	pub receiver: Option<Receiver<FileLoaderAction>>,
+	/// How many icon loads still have to send End to the receiver, searches start one per batch of results
+	pub iconLoads: usize,
+	/// The sending side of receiver for searches, so later batches report to the same receiver
+	pub iconSend: Option<Sender<FileLoaderAction>>,
+	/// Set when this is not a real directory but the results of a search in fullPath
+	pub search: Option<SearchHandle>,
+}
+
+impl DirectoryReader {
+	pub fn make(path: &str, status: &str, files: Vec<UIFile>, receiver: Receiver<FileLoaderAction>) -> Self {
+		let mut pathIndex = HashMap::new();
+		for (i, f) in files.iter().enumerate() {
+			pathIndex.insert(f.fullPath.to_string(), i);
+		}
+		
+		Self {
+			fullPath: path.to_string(),
+			status: status.to_string(),
+			shown: (0..files.len()).collect(),
+			model: Rc::new(VecModel::from(files.clone())),
+			files,
+			pathIndex,
+			filter: NameMatcher::All,
+			receiver: Some(receiver),
+			iconLoads: 1,
+			iconSend: None,
+			search: None,
+		}
+	}
+	
+	/// An initially empty listing that is filled with the results of the search as they come in
+	pub fn makeSearch(root: &str, search: SearchHandle) -> Self {
+		Self {
+			fullPath: root.to_string(),
+			status: String::new(),
+			files: vec![],
+			pathIndex: Default::default(),
+			shown: vec![],
+			model: Rc::new(VecModel::default()),
+			filter: NameMatcher::All,
+			receiver: None,
+			iconLoads: 0,
+			iconSend: None,
+			search: Some(search),
+		}
+	}
+	
+	pub fn isLoading(&self) -> bool {
+		self.receiver.is_some() || self.search.as_ref().map(|s| !s.done).unwrap_or(false)
+	}
+	
+	pub fn push(&mut self, file: UIFile) {
+		let index = self.files.len();
+		self.pathIndex.insert(file.fullPath.to_string(), index);
+		if self.filter.isMatch(&file.name) {
+			self.shown.push(index);
+			self.model.push(file.clone());
+		}
+		self.files.push(file);
+	}
+	
+	pub fn files(&self) -> &[UIFile] { &self.files }
+	
+	/// Moves the entries to their new paths without reloading the directory
+	pub fn renamed(&mut self, renames: &[(PathBuf, PathBuf)]) {
+		// Swaps and rotations reuse names, so every entry is looked up before any of them moves
+		let indices: Vec<Option<usize>> = renames.iter().map(|(old, _)| self.pathIndex.remove(old.to_string_lossy().as_ref())).collect();
+		for (index, (_, new)) in indices.into_iter().zip(renames) {
+			if let Some(index) = index {
+				self.moveEntry(index, new);
+			}
+		}
+	}
+	
+	/// The new name may pass the filter or not anymore
+	fn moveEntry(&mut self, index: usize, new: &Path) {
+		let newPath = new.to_string_lossy().into_owned();
+		self.update(index, |f| {
+			f.fullPath = SharedString::from(newPath.as_str());
+			f.name = SharedString::from(new.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default());
+		});
+		self.pathIndex.insert(newPath, index);
+		
+		match (self.shown.binary_search(&index), self.filter.isMatch(&self.files[index].name)) {
+			(Ok(row), false) => {
+				self.shown.remove(row);
+				self.model.remove(row);
+			}
+			(Err(row), true) => {
+				self.shown.insert(row, index);
+				self.model.insert(row, self.files[index].clone());
+			}
+			_ => {}
+		}
+	}
+	
+	pub fn selectedPaths(&self) -> Vec<PathBuf> {
+		self.files.iter().filter(|f| f.selected).map(|f| PathBuf::from(f.fullPath.as_str())).collect()
+	}
+	
+	/// The entries that pass the filter, in the order they are shown
+	pub fn shownPaths(&self) -> Vec<PathBuf> {
+		self.shown.iter().map(|i| PathBuf::from(self.files[*i].fullPath.as_str())).collect()
+	}
+	
+	pub fn info(&self) -> UIDirectoryInfo {
+		let status = match &self.search {
+			Some(s) if self.files.is_empty() => { if s.done { "Nothing found" } else { "Searching..." } }
+			_ if !self.files.is_empty() && self.shown.is_empty() => { "Nothing matches the filter" }
+			_ => { self.status.as_str() }
+		};
+		UIDirectoryInfo {
+			files: ModelRc::from(self.model.clone() as Rc<dyn Model<Data=UIFile>>),
+			fullPath: SharedString::from(self.fullPath.as_str()),
+			status: SharedString::from(status),
+			virtualName: self.search.as_ref().map(|s| SharedString::from(s.description.as_str())).unwrap_or_default(),
+		}
+	}
+	
+	pub fn statusLine(&self) -> String {
+		let count = if self.filter.isAll() {
+			format!("{} items", self.files.len())
+		} else {
+			format!("{} of {} shown", self.shown.len(), self.files.len())
+		};
+		match &self.search {
+			None => { count }
+			Some(s) => { format!("{} - {}", s.progress(), count) }
+		}
+	}
+	
+	pub fn setFilter(&mut self, filter: NameMatcher) {
+		self.filter = filter;
+		let files = &self.files;
+		let filter = &self.filter;
+		self.shown = (0..files.len()).filter(|i| filter.isMatch(&files[*i].name)).collect();
+		self.rebuildModel();
+	}
+	
+	/// Row of the entry in the UI model, if it passes the filter
+	pub fn shownRow(&self, index: usize) -> Option<usize> {
+		self.shown.binary_search(&index).ok()
+	}
+	
+	pub fn update<F: FnOnce(&mut UIFile)>(&mut self, index: usize, change: F) {
+		let file = match self.files.get_mut(index) {
+			None => { return; }
+			Some(f) => { f }
+		};
+		change(file);
+		if let Ok(row) = self.shown.binary_search(&index) {
+			self.model.set_row_data(row, file.clone());
+		}
+	}
+	
+	/// For changes to many entries at once, the model is rebuilt in one go instead of row by row
+	pub fn updateMany<F: FnMut(usize, &mut UIFile)>(&mut self, indices: impl Iterator<Item=usize>, mut change: F) {
+		for i in indices {
+			if let Some(file) = self.files.get_mut(i) {
+				change(i, file);
+			}
+		}
+		self.rebuildModel();
+	}
+	
+	fn rebuildModel(&self) {
+		self.model.set_vec(self.shown.iter().map(|i| self.files[*i].clone()).collect::<Vec<_>>());
+	}
+}
@@ -0,0 +1,416 @@
+use std::{fs, io, thread};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::trash;
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum OpKind {
+	Move,
+	Copy,
+	Link,
+	/// Dest is not used
+	Trash,
+	/// Dest is the zip file all sources go into
+	Compress,
+}
+
+impl OpKind {
+	fn verb(&self) -> &'static str {
+		match self {
+			OpKind::Move => { "Moving" }
+			OpKind::Copy => { "Copying" }
+			OpKind::Link => { "Linking" }
+			OpKind::Trash => { "Moving" }
+			OpKind::Compress => { "Compressing" }
+		}
+	}
+	
+	fn pastVerb(&self) -> &'static str {
+		match self {
+			OpKind::Move => { "Moved" }
+			OpKind::Copy => { "Copied" }
+			OpKind::Link => { "Linked" }
+			OpKind::Trash => { "Trashed" }
+			OpKind::Compress => { "Compressed" }
+		}
+	}
+}
+
+/// Puts every source into the dest directory, or the dest archive
+pub struct Operation {
+	pub kind: OpKind,
+	pub sources: Vec<PathBuf>,
+	pub dest: PathBuf,
+}
+
+pub enum OpEvent {
+	Progress(usize),
+	/// Every source with where it ended up, and what went wrong with the rest
+	Finished(Vec<(PathBuf, PathBuf)>, Vec<String>),
+}
+
+/// An operation running in the background
+pub struct RunningOp {
+	pub kind: OpKind,
+	pub dest: PathBuf,
+	pub total: usize,
+	pub done: usize,
+	pub receiver: Receiver<OpEvent>,
+	/// Set when pasting cut files, they leave the clipboard once all of them moved
+	pub pastedCut: Option<Vec<PathBuf>>,
+}
+
+impl RunningOp {
+	pub fn progress(&self) -> String {
+		let dest = match self.kind {
+			OpKind::Trash => { "the trash".to_string() }
+			OpKind::Compress => { self.dest.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default() }
+			_ => { self.dest.display().to_string() }
+		};
+		let to = if self.kind == OpKind::Compress { "into" } else { "to" };
+		format!("{} {} of {} items {} {}", self.kind.verb(), self.done + 1, self.total, to, dest)
+	}
+	
+	pub fn summary(&self, done: &[(PathBuf, PathBuf)], errors: &[String]) -> String {
+		let mut res = format!("{} {} of {} items", self.kind.pastVerb(), done.len(), self.total);
+		if !errors.is_empty() {
+			res += &format!(", {} failed: {}", errors.len(), errors.join("; "));
+		}
+		res
+	}
+}
+
+pub fn start(op: Operation) -> RunningOp {
+	let (send, receiver) = channel();
+	let running = RunningOp {
+		kind: op.kind,
+		dest: op.dest.clone(),
+		total: op.sources.len(),
+		done: 0,
+		receiver,
+		pastedCut: None,
+	};
+	
+	thread::Builder::new().name("File operation".into()).spawn(move || {
+		run(op, send);
+	}).map_err(|err| println!("Failed to start file operation: {}", err)).ok();
+	running
+}
+
+fn run(op: Operation, send: Sender<OpEvent>) {
+	if op.kind == OpKind::Compress {
+		compress(op, send);
+		return;
+	}
+	let mut done = vec![];
+	let mut errors = vec![];
+	
+	for (i, src) in op.sources.iter().enumerate() {
+		let _ = send.send(OpEvent::Progress(i));
+		match apply(op.kind, src, &op.dest) {
+			Ok(Some(target)) => { done.push((src.clone(), target)); }
+			Ok(None) => {}
+			Err(err) => { errors.push(format!("{}: {}", src.display(), err)); }
+		}
+	}
+	let _ = send.send(OpEvent::Finished(done, errors));
+}
+
+/// The path the source ended up at, None if there was nothing to do
+fn apply(kind: OpKind, src: &Path, dest: &Path) -> Result<Option<PathBuf>, String> {
+	if kind == OpKind::Trash {
+		return trash::trash(src).map(Some);
+	}
+	let name = src.file_name().ok_or("Can not use a root directory")?;
+	if dest.starts_with(src) {
+		return Err("Can not put a folder inside of itself".to_string());
+	}
+	if !dest.is_dir() {
+		return Err(format!("{} is not a folder", dest.display()));
+	}
+	
+	let mut target = dest.join(name);
+	match kind {
+		OpKind::Move => {
+			if src.parent() == Some(dest) {
+				return Ok(None);
+			}
+			checkFree(&target)?;
+			moveTo(src, &target).map_err(|err| err.to_string())?;
+		}
+		OpKind::Copy => {
+			target = uniqueName(&target);
+			copyTree(src, &target).map_err(|err| err.to_string())?;
+		}
+		OpKind::Link => {
+			checkFree(&target)?;
+			symlink(src, &target).map_err(|err| err.to_string())?;
+		}
+		OpKind::Trash | OpKind::Compress => {}
+	}
+	Ok(Some(target))
+}
+
+/// Every source goes into the root of the archive. Nothing is left behind if no source could be added.
+fn compress(op: Operation, send: Sender<OpEvent>) {
+	let mut done = vec![];
+	let mut errors = vec![];
+	let file = match fs::OpenOptions::new().write(true).create_new(true).open(&op.dest) {
+		Ok(f) => { f }
+		Err(err) => {
+			let _ = send.send(OpEvent::Finished(done, vec![format!("{}: {}", op.dest.display(), err)]));
+			return;
+		}
+	};
+	let mut zip = zip::ZipWriter::new(io::BufWriter::new(file));
+	
+	for (i, src) in op.sources.iter().enumerate() {
+		let _ = send.send(OpEvent::Progress(i));
+		let name = match src.file_name() {
+			Some(n) => { n.to_string_lossy().into_owned() }
+			None => {
+				errors.push(format!("{}: Can not use a root directory", src.display()));
+				continue;
+			}
+		};
+		match addToZip(&mut zip, src, &name) {
+			Ok(_) => { done.push((src.clone(), op.dest.clone())); }
+			Err(err) => { errors.push(format!("{}: {}", src.display(), err)); }
+		}
+	}
+	
+	if let Err(err) = zip.finish() {
+		errors.push(format!("{}: {}", op.dest.display(), err));
+		done.clear();
+	}
+	if done.is_empty() {
+		fs::remove_file(&op.dest).ok();
+	}
+	let _ = send.send(OpEvent::Finished(done, errors));
+}
+
+/// Symlinks are stored as links, not followed
+fn addToZip<W: io::Write + io::Seek>(zip: &mut zip::ZipWriter<W>, src: &Path, name: &str) -> Result<(), String> {
+	let meta = fs::symlink_metadata(src).map_err(|err| err.to_string())?;
+	let mut options = zip::write::FileOptions::default().large_file(meta.len() >= u32::MAX as u64);
+	#[cfg(unix)] {
+		use std::os::unix::fs::PermissionsExt;
+		options = options.unix_permissions(meta.permissions().mode() & 0o7777);
+	}
+	
+	if meta.file_type().is_symlink() {
+		let target = fs::read_link(src).map_err(|err| err.to_string())?;
+		return zip.add_symlink(name, target.to_string_lossy(), options).map_err(|err| err.to_string());
+	}
+	if !meta.is_dir() {
+		zip.start_file(name, options).map_err(|err| err.to_string())?;
+		let mut file = fs::File::open(src).map_err(|err| err.to_string())?;
+		io::copy(&mut file, zip).map_err(|err| err.to_string())?;
+		return Ok(());
+	}
+	
+	zip.add_directory(format!("{}/", name), options).map_err(|err| err.to_string())?;
+	let mut entries: Vec<fs::DirEntry> = fs::read_dir(src).map_err(|err| err.to_string())?.flatten().collect();
+	entries.sort_by_key(|e| e.file_name());
+	for entry in entries {
+		addToZip(zip, &entry.path(), &format!("{}/{}", name, entry.file_name().to_string_lossy()))?;
+	}
+	Ok(())
+}
+
+fn checkFree(target: &Path) -> Result<(), String> {
+	if fs::symlink_metadata(target).is_ok() {
+		return Err(format!("{} already exists", target.display()));
+	}
+	Ok(())
+}
+
+/// Renames when possible and copies and deletes when the target is on another filesystem. An existing target is never replaced.
+pub fn moveTo(src: &Path, target: &Path) -> io::Result<()> {
+	match renameNoReplace(src, target) {
+		Ok(_) => { Ok(()) }
+		Err(err) if isCrossDevice(&err) => {
+			copyTree(src, target)?;
+			removeTree(src)
+		}
+		Err(err) => { Err(err) }
+	}
+}
+
+#[cfg(unix)]
+fn isCrossDevice(err: &io::Error) -> bool {
+	err.raw_os_error() == Some(libc::EXDEV)
+}
+
+#[cfg(windows)]
+fn isCrossDevice(err: &io::Error) -> bool {
+	const ERROR_NOT_SAME_DEVICE: i32 = 17;
+	err.raw_os_error() == Some(ERROR_NOT_SAME_DEVICE)
+}
+
+/// Like fs::rename, but fails with AlreadyExists instead of replacing the target
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+pub fn renameNoReplace(src: &Path, target: &Path) -> io::Result<()> {
+	use std::ffi::CString;
+	use std::os::unix::ffi::OsStrExt;
+	
+	let from = CString::new(src.as_os_str().as_bytes())?;
+	let to = CString::new(target.as_os_str().as_bytes())?;
+	if unsafe { libc::renameat2(libc::AT_FDCWD, from.as_ptr(), libc::AT_FDCWD, to.as_ptr(), libc::RENAME_NOREPLACE) } == 0 {
+		return Ok(());
+	}
+	let err = io::Error::last_os_error();
+	match err.raw_os_error() {
+		// Older kernels and filesystems that don't support the flag
+		Some(libc::EINVAL) | Some(libc::ENOSYS) => { renameChecked(src, target) }
+		_ => { Err(err) }
+	}
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+pub fn renameNoReplace(src: &Path, target: &Path) -> io::Result<()> {
+	renameChecked(src, target)
+}
+
+/// Without renameat2 a target created right between the check and the rename is still replaced
+fn renameChecked(src: &Path, target: &Path) -> io::Result<()> {
+	if fs::symlink_metadata(target).is_ok() {
+		return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target.display())));
+	}
+	fs::rename(src, target)
+}
+
+/// Symlinks are copied as links, not followed. Nothing is overwritten, and a copy that fails partway is removed again.
+pub fn copyTree(src: &Path, target: &Path) -> io::Result<()> {
+	let mut created = false;
+	let res = copyEntry(src, target, &mut created);
+	if res.is_err() && created {
+		removeTree(target).map_err(|err| println!("Failed to remove the partial copy {}: {}", target.display(), err)).ok();
+	}
+	res
+}
+
+/// Sets created once target exists, everything after that belongs to the copy
+fn copyEntry(src: &Path, target: &Path, created: &mut bool) -> io::Result<()> {
+	let meta = fs::symlink_metadata(src)?;
+	if meta.file_type().is_symlink() {
+		symlink(&fs::read_link(src)?, target)?;
+		*created = true;
+		return Ok(());
+	}
+	if !meta.is_dir() {
+		let mut to = fs::OpenOptions::new().write(true).create_new(true).open(target)?;
+		*created = true;
+		io::copy(&mut fs::File::open(src)?, &mut to)?;
+		return to.set_permissions(meta.permissions());
+	}
+	
+	fs::create_dir(target)?;
+	*created = true;
+	for entry in fs::read_dir(src)? {
+		let entry = entry?;
+		copyEntry(&entry.path(), &target.join(entry.file_name()), &mut false)?;
+	}
+	Ok(())
+}
+
+pub fn removeTree(path: &Path) -> io::Result<()> {
+	let meta = fs::symlink_metadata(path)?;
+	if meta.is_dir() {
+		fs::remove_dir_all(path)
+	} else {
+		fs::remove_file(path)
+	}
+}
+
+#[cfg(unix)]
+pub fn symlink(src: &Path, target: &Path) -> io::Result<()> {
+	std::os::unix::fs::symlink(src, target)
+}
+
+#[cfg(windows)]
+pub fn symlink(src: &Path, target: &Path) -> io::Result<()> {
+	if src.is_dir() {
+		std::os::windows::fs::symlink_dir(src, target)
+	} else {
+		std::os::windows::fs::symlink_file(src, target)
+	}
+}
+
+/// Ok if the file at path can be renamed to name, which has to be valid and not taken
+pub fn validateName(path: &Path, name: &str) -> Result<(), String> {
+	checkName(name)?;
+	
+	let target = path.with_file_name(name);
+	if target != path && fs::symlink_metadata(&target).is_ok() {
+		// Changing only the case on a case insensitive filesystem finds the file itself
+		let same = match (fs::canonicalize(&target), fs::canonicalize(path)) {
+			(Ok(a), Ok(b)) => { a == b && target.file_name() != path.file_name() }
+			_ => { false }
+		};
+		if !same {
+			return Err(format!("{} already exists", name));
+		}
+	}
+	Ok(())
+}
+
+/// Path separators and characters Windows can not store are refused
+pub fn checkName(name: &str) -> Result<(), String> {
+	if name.trim().is_empty() {
+		return Err("The name can not be empty".to_string());
+	}
+	if name == "." || name == ".." {
+		return Err(format!("\"{}\" is not a valid name", name));
+	}
+	let forbidden: &[char] = if cfg!(windows) { &['<', '>', ':', '"', '/', '\\', '|', '?', '*'] } else { &['/'] };
+	if let Some(c) = name.chars().find(|c| forbidden.contains(c) || c.is_control()) {
+		return Err(if c.is_control() { "The name can not contain control characters".to_string() } else { format!("The name can not contain {}", c) });
+	}
+	if cfg!(windows) && (name.ends_with('.') || name.ends_with(' ')) {
+		return Err("The name can not end with a dot or space".to_string());
+	}
+	Ok(())
+}
+
+/// The new path of the file
+pub fn rename(path: &Path, name: &str) -> Result<PathBuf, String> {
+	validateName(path, name)?;
+	let target = path.with_file_name(name);
+	let fail = |err: io::Error| format!("Failed to rename {}: {}", path.display(), err);
+	let caseOnly = path.file_name().map(|n| n.to_string_lossy() != name && n.to_string_lossy().to_lowercase() == name.to_lowercase()).unwrap_or(false);
+	if !caseOnly {
+		renameNoReplace(path, &target).map_err(fail)?;
+		return Ok(target);
+	}
+	
+	// A case insensitive filesystem sees the target as the file itself, so it takes a detour over a temporary name
+	let temp = uniqueName(&path.with_file_name(format!(".{}.rename", name)));
+	renameNoReplace(path, &temp).map_err(fail)?;
+	if let Err(err) = renameNoReplace(&temp, &target) {
+		renameNoReplace(&temp, path).map_err(|err| println!("Failed to move {} back to {}: {}", temp.display(), path.display(), err)).ok();
+		return Err(fail(err));
+	}
+	Ok(target)
+}
+
+/// "name (copy).ext", "name (copy 2).ext", ... if path is taken
+pub fn uniqueName(path: &Path) -> PathBuf {
+	if fs::symlink_metadata(path).is_err() {
+		return path.to_path_buf();
+	}
+	let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+	let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+	let dir = path.parent().unwrap_or(Path::new(""));
+	
+	let mut n = 1;
+	loop {
+		let name = if n == 1 { format!("{} (copy){}", stem, ext) } else { format!("{} (copy {}){}", stem, n, ext) };
+		let candidate = dir.join(name);
+		if fs::symlink_metadata(&candidate).is_err() {
+			return candidate;
+		}
+		n += 1;
+	}
+}
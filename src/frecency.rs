@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+
+use crate::config::configFile;
+
+lazy_static! {
+	static ref VISITS: Mutex<Option<Vec<Visit>>> = Mutex::new(None);
+}
+
+/// Once the ranks add up to more than this, all of them are scaled down and the ones that drop below 1 are forgotten
+const MAX_TOTAL_RANK: f64 = 10000.0;
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug, Clone)]
+struct Visit {
+	path: String,
+	rank: f64,
+	/// Seconds since the unix epoch
+	lastAccess: u64,
+}
+
+impl Visit {
+	/// Same weighting as zoxide, recent visits count a lot more
+	fn score(&self, now: u64) -> f64 {
+		let age = now.saturating_sub(self.lastAccess);
+		let factor = if age < 60 * 60 {
+			4.0
+		} else if age < 24 * 60 * 60 {
+			2.0
+		} else if age < 7 * 24 * 60 * 60 {
+			0.5
+		} else {
+			0.25
+		};
+		self.rank * factor
+	}
+}
+
+fn now() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn file() -> PathBuf {
+	configFile("Frecency.json")
+}
+
+fn withVisits<R, F: FnOnce(&mut Vec<Visit>) -> R>(f: F) -> R {
+	let mut visits = VISITS.lock().unwrap();
+	let visits = visits.get_or_insert_with(|| {
+		fs::read_to_string(file())
+			.ok()
+			.and_then(|data| serde_json::from_str(&data).map_err(|err| {
+				println!("Frecency data malformed: {}", err);
+			}).ok())
+			.unwrap_or_default()
+	});
+	f(visits)
+}
+
+/// Records that a directory was opened
+pub fn visit(path: &str) {
+	if path.is_empty() {
+		return;
+	}
+	withVisits(|visits| {
+		let now = now();
+		match visits.iter_mut().find(|v| v.path == path) {
+			Some(v) => {
+				v.rank += 1.0;
+				v.lastAccess = now;
+			}
+			None => {
+				visits.push(Visit { path: path.to_string(), rank: 1.0, lastAccess: now });
+			}
+		}
+		
+		if visits.iter().map(|v| v.rank).sum::<f64>() > MAX_TOTAL_RANK {
+			for v in visits.iter_mut() {
+				v.rank *= 0.9;
+			}
+			visits.retain(|v| v.rank >= 1.0);
+		}
+		
+		fs::write(file(), serde_json::to_string(visits).unwrap()).map_err(|err| {
+			println!("Failed to save frecency data: {}", err);
+		}).ok();
+	});
+}
+
+/// Visited directories that still exist with their frecency, highest first
+pub fn scored() -> Vec<(String, f64)> {
+	withVisits(|visits| {
+		let now = now();
+		let mut res: Vec<(String, f64)> = visits.iter()
+		                                        .filter(|v| Path::new(&v.path).is_dir())
+		                                        .map(|v| (v.path.clone(), v.score(now)))
+		                                        .collect();
+		res.sort_by(|a, b| b.1.total_cmp(&a.1));
+		res
+	})
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
+
+/// Bigger files are skipped, they are almost never text and would have to be read as a whole
+const MAX_FILE_SIZE: u64 = 32 * 1024 * 1024;
+/// How much of the start of a file is checked for NUL bytes
+const BINARY_PROBE: usize = 8 * 1024;
+/// Snippets kept per file, the rest are only counted
+const MAX_SNIPPETS: usize = 3;
+const MAX_SNIPPET_LEN: usize = 160;
+
+/// Searches text contents for a literal or a regex. Like names, the search is case insensitive unless the pattern has an upper case letter.
+pub struct ContentMatcher {
+	regex: Regex,
+}
+
+impl ContentMatcher {
+	pub fn new(pattern: &str, isRegex: bool) -> Result<Self, String> {
+		let source = if isRegex { pattern.to_string() } else { regex::escape(pattern) };
+		let regex = RegexBuilder::new(&source)
+			.case_insensitive(!pattern.chars().any(|c| c.is_uppercase()))
+			.build()
+			.map_err(|err| format!("Invalid regex: {}", err))?;
+		Ok(Self { regex })
+	}
+	
+	/// Matching lines of a file as "line: text". Files inside zip archives are searched too, their snippets start with the entry name.
+	pub fn searchFile(&self, path: &Path) -> Vec<String> {
+		let isZip = path.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("zip")).unwrap_or(false);
+		if isZip {
+			return self.searchZip(path);
+		}
+		
+		let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+		if size == 0 || size > MAX_FILE_SIZE {
+			return vec![];
+		}
+		match fs::read(path) {
+			Ok(data) => { self.searchBytes(&data, "") }
+			Err(_) => { vec![] }
+		}
+	}
+	
+	fn searchZip(&self, path: &Path) -> Vec<String> {
+		let mut archive = match File::open(path).map_err(|err| err.to_string())
+		                                        .and_then(|f| zip::ZipArchive::new(f).map_err(|err| err.to_string())) {
+			Ok(a) => { a }
+			Err(_) => { return vec![]; }
+		};
+		
+		let mut res = vec![];
+		for i in 0..archive.len() {
+			let mut entry = match archive.by_index(i) {
+				Ok(e) => { e }
+				Err(_) => { continue; }
+			};
+			if entry.is_dir() || entry.size() == 0 || entry.size() > MAX_FILE_SIZE {
+				continue;
+			}
+			
+			let name = entry.name().to_string();
+			let mut data = Vec::with_capacity(entry.size() as usize);
+			if entry.read_to_end(&mut data).is_err() {
+				continue;
+			}
+			res.extend(self.searchBytes(&data, &format!("{}:", name)));
+			if res.len() > MAX_SNIPPETS {
+				break;
+			}
+		}
+		res
+	}
+	
+	fn searchBytes(&self, data: &[u8], prefix: &str) -> Vec<String> {
+		let text = match decode(data) {
+			None => { return vec![]; }
+			Some(t) => { t }
+		};
+		
+		let mut res = vec![];
+		let mut more = 0;
+		for (i, line) in text.lines().enumerate() {
+			if !self.regex.is_match(line) {
+				continue;
+			}
+			if res.len() < MAX_SNIPPETS {
+				res.push(format!("{}{}: {}", prefix, i + 1, shorten(line.trim())));
+			} else {
+				more += 1;
+			}
+		}
+		if more > 0 {
+			res.push(format!("{}+{} more", prefix, more));
+		}
+		res
+	}
+}
+
+/// Text by byte order mark, UTF-8 without one. None for binary files.
+fn decode(data: &[u8]) -> Option<String> {
+	if let Some(rest) = data.strip_prefix(&[0xFF, 0xFE]) {
+		return Some(decodeUtf16(rest, u16::from_le_bytes));
+	}
+	if let Some(rest) = data.strip_prefix(&[0xFE, 0xFF]) {
+		return Some(decodeUtf16(rest, u16::from_be_bytes));
+	}
+	let data = data.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(data);
+	
+	if data[..data.len().min(BINARY_PROBE)].contains(&0) {
+		return None;
+	}
+	Some(String::from_utf8_lossy(data).into_owned())
+}
+
+fn decodeUtf16(data: &[u8], toUnit: fn([u8; 2]) -> u16) -> String {
+	let units: Vec<u16> = data.chunks_exact(2).map(|c| toUnit([c[0], c[1]])).collect();
+	String::from_utf16_lossy(&units)
+}
+
+fn shorten(line: &str) -> String {
+	match line.char_indices().nth(MAX_SNIPPET_LEN) {
+		None => { line.to_string() }
+		Some((end, _)) => { format!("{}…", &line[..end]) }
+	}
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Style {
+	Plain,
+	Keyword,
+	String,
+	Comment,
+	Number,
+}
+
+impl Style {
+	/// The index UIPreviewSpan.kind uses
+	pub fn index(&self) -> i32 {
+		match self {
+			Style::Plain => { 0 }
+			Style::Keyword => { 1 }
+			Style::String => { 2 }
+			Style::Comment => { 3 }
+			Style::Number => { 4 }
+		}
+	}
+}
+
+/// Just enough about a language to color it, this is not a parser
+pub struct Language {
+	keywords: &'static [&'static str],
+	lineComments: &'static [&'static str],
+	blockComment: Option<(&'static str, &'static str)>,
+	quotes: &'static [char],
+	/// SQL keywords are written in any case
+	ignoreCase: bool,
+}
+
+const C_LIKE: Language = Language {
+	keywords: &["abstract", "as", "async", "auto", "await", "bool", "break", "case", "catch", "char", "class", "const", "continue", "crate", "default",
+		"defer", "delete", "do", "double", "dyn", "else", "enum", "export", "extends", "extern", "false", "final", "finally", "float", "fn", "for", "func",
+		"function", "go", "goto", "if", "impl", "implements", "import", "in", "include", "inline", "instanceof", "int", "interface", "let", "long", "loop",
+		"match", "mod", "move", "mut", "namespace", "new", "nil", "null", "override", "package", "private", "protected", "pub", "public", "ref", "return",
+		"self", "short", "signed", "static", "struct", "super", "switch", "template", "this", "throw", "throws", "trait", "true", "try", "type", "typedef",
+		"typeof", "union", "unsafe", "unsigned", "use", "using", "val", "var", "virtual", "void", "volatile", "where", "while", "yield"],
+	lineComments: &["//"],
+	blockComment: Some(("/*", "*/")),
+	quotes: &['"', '\'', '`'],
+	ignoreCase: false,
+};
+
+const PYTHON: Language = Language {
+	keywords: &["and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else", "except", "False", "finally", "for",
+		"from", "global", "if", "import", "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "self", "True", "try", "while",
+		"with", "yield"],
+	lineComments: &["#"],
+	blockComment: None,
+	quotes: &['"', '\''],
+	ignoreCase: false,
+};
+
+const SHELL: Language = Language {
+	keywords: &["case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local", "return", "then", "until", "while"],
+	lineComments: &["#"],
+	blockComment: None,
+	quotes: &['"', '\''],
+	ignoreCase: false,
+};
+
+/// TOML, YAML, INI and other configuration files
+const CONFIG: Language = Language {
+	keywords: &["true", "false", "yes", "no", "null", "on", "off"],
+	lineComments: &["#", ";"],
+	blockComment: None,
+	quotes: &['"', '\''],
+	ignoreCase: false,
+};
+
+const SQL: Language = Language {
+	keywords: &["AND", "AS", "BY", "CREATE", "DELETE", "DROP", "FROM", "GROUP", "INSERT", "INTO", "JOIN", "KEY", "LEFT", "LIMIT", "NOT", "NULL", "ON",
+		"OR", "ORDER", "PRIMARY", "SELECT", "SET", "TABLE", "UPDATE", "VALUES", "WHERE"],
+	lineComments: &["--"],
+	blockComment: Some(("/*", "*/")),
+	quotes: &['"', '\''],
+	ignoreCase: true,
+};
+
+const LUA: Language = Language {
+	keywords: &["and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local", "nil", "not", "or", "repeat", "return",
+		"then", "true", "until", "while"],
+	lineComments: &["--"],
+	blockComment: None,
+	quotes: &['"', '\''],
+	ignoreCase: false,
+};
+
+const MARKUP: Language = Language {
+	keywords: &[],
+	lineComments: &[],
+	blockComment: Some(("<!--", "-->")),
+	quotes: &['"'],
+	ignoreCase: false,
+};
+
+/// Picked by extension, None for plain text
+pub fn languageFor(path: &Path) -> Option<&'static Language> {
+	let ext = path.extension()?.to_string_lossy().to_lowercase();
+	Some(match ext.as_str() {
+		"rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cxx" | "java" | "kt" | "kts" | "js" | "mjs" | "ts" | "tsx" | "jsx" | "go" | "cs" | "swift" | "scala"
+		| "dart" | "slint" | "css" | "scss" | "json" | "groovy" | "gradle" | "php" | "zig" => { &C_LIKE }
+		"py" | "pyw" | "rb" | "pl" | "r" | "cmake" => { &PYTHON }
+		"sh" | "bash" | "zsh" | "fish" => { &SHELL }
+		"toml" | "yaml" | "yml" | "ini" | "cfg" | "conf" | "desktop" | "properties" | "env" => { &CONFIG }
+		"sql" => { &SQL }
+		"lua" => { &LUA }
+		"html" | "htm" | "xml" | "svg" | "xhtml" | "vue" => { &MARKUP }
+		_ => { return None; }
+	})
+}
+
+/// The lines of the text split into styled spans. Block comments carry over between lines, strings do not.
+pub fn highlight(text: &str, language: Option<&Language>) -> Vec<Vec<(Style, String)>> {
+	let language = match language {
+		None => { return text.lines().map(|l| vec![(Style::Plain, l.to_string())]).collect(); }
+		Some(l) => { l }
+	};
+	let mut inBlock = false;
+	text.lines().map(|line| highlightLine(line, language, &mut inBlock)).collect()
+}
+
+fn highlightLine(line: &str, lang: &Language, inBlock: &mut bool) -> Vec<(Style, String)> {
+	let mut spans: Vec<(Style, String)> = vec![];
+	let mut push = |style: Style, text: &str| {
+		match spans.last_mut() {
+			Some((s, t)) if *s == style => { t.push_str(text); }
+			_ => { spans.push((style, text.to_string())); }
+		}
+	};
+	
+	let mut i = 0;
+	while i < line.len() {
+		let rest = &line[i..];
+		if *inBlock {
+			let end = lang.blockComment.and_then(|(_, end)| rest.find(end).map(|e| e + end.len()));
+			match end {
+				Some(e) => {
+					push(Style::Comment, &rest[..e]);
+					*inBlock = false;
+					i += e;
+				}
+				None => {
+					push(Style::Comment, rest);
+					i = line.len();
+				}
+			}
+			continue;
+		}
+		if lang.lineComments.iter().any(|c| rest.starts_with(c)) {
+			push(Style::Comment, rest);
+			break;
+		}
+		if let Some((start, _)) = lang.blockComment.filter(|(start, _)| rest.starts_with(start)) {
+			push(Style::Comment, start);
+			*inBlock = true;
+			i += start.len();
+			continue;
+		}
+		
+		let c = rest.chars().next().unwrap();
+		let len = if lang.quotes.contains(&c) {
+			// Up to the closing quote that is not escaped, or the end of the line
+			let mut escaped = false;
+			let end = rest.char_indices().skip(1).find(|(_, ch)| {
+				let closes = !escaped && *ch == c;
+				escaped = !escaped && *ch == '\\';
+				closes
+			}).map(|(j, ch)| j + ch.len_utf8()).unwrap_or(rest.len());
+			push(Style::String, &rest[..end]);
+			end
+		} else if c.is_ascii_digit() {
+			let end = rest.find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '.' || ch == '_')).unwrap_or(rest.len());
+			push(Style::Number, &rest[..end]);
+			end
+		} else if c.is_alphabetic() || c == '_' {
+			let end = rest.find(|ch: char| !(ch.is_alphanumeric() || ch == '_')).unwrap_or(rest.len());
+			let word = &rest[..end];
+			let isKeyword = lang.keywords.iter().any(|k| *k == word || (lang.ignoreCase && k.eq_ignore_ascii_case(word)));
+			push(if isKeyword { Style::Keyword } else { Style::Plain }, word);
+			end
+		} else {
+			push(Style::Plain, &rest[..c.len_utf8()]);
+			c.len_utf8()
+		};
+		i += len;
+	}
+	spans
+}
@@ -1,19 +1,30 @@
 use std::{fmt, fs, thread};
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
-use std::sync::mpsc::Sender;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::thread::sleep;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 use rand::Rng;
+use slint::Image;
 
-use crate::rgba_img::RgbImg;
+use crate::rgba_img::{ImageSequence, RgbImg};
 use crate::work;
 
+/// Limits for one animated thumbnail, longer animations are cut off
+const ANIMATION_FRAMES: usize = 120;
+const ANIMATION_BYTES: usize = 32 * 1024 * 1024;
+/// How many decoded animations a window keeps, the ones hovered least recently go first
+const ANIMATIONS_KEPT: usize = 16;
+
 type WorkID = u32;
 
+/// Identifies an independent consumer of icons (usually a window) so loading a folder in one does not cancel the others
+pub type IconSlot = u32;
+
 enum LoadStage<T> {
 	Loading,
 	Loaded(SystemTime, T),
@@ -29,7 +40,9 @@ pub struct GlobalIcons {
 	pub folder: Arc<RgbImg>,
 	
 	iconCache: HashMap<String, LoadStage<Arc<RgbImg>>>,
-	workerId: WorkID,
+	workerIds: HashMap<IconSlot, WorkID>,
+	lastWorkerId: WorkID,
+	lastSlot: IconSlot,
 }
 
 impl GlobalIcons {
@@ -40,7 +53,27 @@ impl GlobalIcons {
 			folder: folder.join().unwrap(),
 			default: default.join().unwrap(),
 			iconCache: Default::default(),
-			workerId: Default::default(),
+			workerIds: Default::default(),
+			lastWorkerId: Default::default(),
+			lastSlot: Default::default(),
+		}
+	}
+}
+
+impl GlobalIcons {
+	/// Keeps the loaded icons of renamed files, unless the extension changed and with it what the icon would be
+	pub fn renamed(&mut self, renames: &[(PathBuf, PathBuf)]) {
+		// All are taken out first, a swap would otherwise overwrite the icon it takes next
+		let stages: Vec<_> = renames.iter().map(|(old, _)| self.iconCache.remove(old.to_string_lossy().as_ref())).collect();
+		for (stage, (old, new)) in stages.into_iter().zip(renames) {
+			let stage = match stage {
+				Some(s @ LoadStage::Loaded(..)) => { s }
+				_ => { continue; }
+			};
+			let ext = |p: &Path| p.extension().map(|e| e.to_ascii_lowercase());
+			if ext(old) == ext(new) {
+				self.iconCache.insert(new.to_string_lossy().into_owned(), stage);
+			}
 		}
 	}
 }
@@ -71,88 +104,116 @@ impl Display for FileLoaderAction {
 }
 
 impl GlobalIcons {
-	fn newId(&mut self) -> WorkID {
-		self.workerId += 1;
-		self.workerId
+	pub fn newSlot(&mut self) -> IconSlot {
+		self.lastSlot += 1;
+		self.lastSlot
+	}
+	
+	fn newId(&mut self, slot: IconSlot) -> WorkID {
+		self.lastWorkerId += 1;
+		self.workerIds.insert(slot, self.lastWorkerId);
+		self.lastWorkerId
 	}
 }
 
-pub fn loadAsyncIcons(global: Arc<RwLock<GlobalIcons>>, paths: Arc<Vec<PathBuf>>, send: Sender<FileLoaderAction>) {
-	let workId = { global.write().unwrap().newId() };
-	
+pub fn loadAsyncIcons(global: Arc<RwLock<GlobalIcons>>, slot: IconSlot, paths: Arc<Vec<PathBuf>>, send: Sender<FileLoaderAction>) {
+	let workId = { global.write().unwrap().newId(slot) };
+	loadIcons(global, slot, workId, paths, send);
+}
+
+/// Like loadAsyncIcons, but the loads already running for the slot carry on. Every call sends its own End.
+pub fn loadMoreIcons(global: Arc<RwLock<GlobalIcons>>, slot: IconSlot, paths: Arc<Vec<PathBuf>>, send: Sender<FileLoaderAction>) {
+	let workId = {
+		let mut global = global.write().unwrap();
+		match global.workerIds.get(&slot) {
+			Some(id) => { *id }
+			None => { global.newId(slot) }
+		}
+	};
+	loadIcons(global, slot, workId, paths, send);
+}
+
+/// Sends exactly one End per call, whether the loads finish or are superseded
+fn loadIcons(global: Arc<RwLock<GlobalIcons>>, slot: IconSlot, workId: WorkID, paths: Arc<Vec<PathBuf>>, send: Sender<FileLoaderAction>) {
 	thread::spawn(move || {
-		if checkID(global.clone(), workId, send.clone()) { return; }
-		
-		let mut toScan = HashSet::new();
-		let mut toSpawn = vec![];
+		scanIcons(global, slot, workId, paths, &send);
+		let _ = send.send(FileLoaderAction::End);
+	});
+}
+
+fn scanIcons(global: Arc<RwLock<GlobalIcons>>, slot: IconSlot, workId: WorkID, paths: Arc<Vec<PathBuf>>, send: &Sender<FileLoaderAction>) {
+	if checkID(&global, slot, workId) { return; }
+	
+	let mut toScan = HashSet::new();
+	let mut toSpawn = vec![];
+	
+	for x in paths.iter() {
+		let pathStr = x.to_str().unwrap();
+		let mut global = global.write().unwrap();
 		
-		for x in paths.iter() {
-			let pathStr = x.to_str().unwrap();
-			let mut global = global.write().unwrap();
-			
-			if let Some(stage) = global.iconCache.get_mut(pathStr) {
-				let pathStr = pathStr.to_string();
-				if let LoadStage::Loading = stage { toScan.insert(pathStr.clone()); }
-				sendStage(&send, pathStr, stage);
-				continue;
-			}
+		if let Some(stage) = global.iconCache.get_mut(pathStr) {
 			let pathStr = pathStr.to_string();
-			toSpawn.push(x.clone());
-			global.iconCache.insert(pathStr.clone(), LoadStage::Loading);
-			toScan.insert(pathStr);
-		}
-		
-		if checkID(global.clone(), workId, send.clone()) { return; }
-		
-		for path in toSpawn {
-			let send = send.clone();
-			let global = global.clone();
-			work::execute(move || {
-				let pathStr = path.to_str().unwrap().to_string();
-				
-				if checkID(global.clone(), workId, send.clone()) {
-					let mut state = global.write().unwrap();
-					state.iconCache.remove(&pathStr);
-					return;
-				}
-				
-				let img = loadFromPath(global.clone(), path.clone());
-				{
-					let state = global.clone();
-					let mut state = state.write().unwrap();
-					state.iconCache.insert(pathStr.clone(), LoadStage::Loaded(SystemTime::now(), img.clone()));
-				}
-				
-				// println!("Async loaded {}", pathStr);
-				
-				if !img.isDefault() {
-					if checkID(global.clone(), workId, send.clone()) { return; }
-					let _ = send.send(FileLoaderAction::UpdateFile(LoadedIcon {
-						image: img,
-						path: pathStr,
-					}));
-				}
-			});
+			if let LoadStage::Loading = stage { toScan.insert(pathStr.clone()); }
+			sendStage(send, pathStr, stage);
+			continue;
 		}
-		
-		while !toScan.is_empty() {
-			sleep(Duration::from_millis(2));
+		let pathStr = pathStr.to_string();
+		toSpawn.push(x.clone());
+		global.iconCache.insert(pathStr.clone(), LoadStage::Loading);
+		toScan.insert(pathStr);
+	}
+	
+	if checkID(&global, slot, workId) { return; }
+	
+	for path in toSpawn {
+		let send = send.clone();
+		let global = global.clone();
+		work::execute(move || {
+			let pathStr = path.to_str().unwrap().to_string();
 			
-			toScan.retain(|f| {
-				match global.clone().write().unwrap().iconCache.get_mut(f) {
-					None => {}
-					Some(stage) => {
-						sendStage(&send, f.clone(), stage);
-						if let LoadStage::Loaded(_, _) = stage {
-							return false;
-						}
+			if checkID(&global, slot, workId) {
+				let mut state = global.write().unwrap();
+				state.iconCache.remove(&pathStr);
+				return;
+			}
+			
+			let img = loadFromPath(global.clone(), path.clone());
+			{
+				let state = global.clone();
+				let mut state = state.write().unwrap();
+				state.iconCache.insert(pathStr.clone(), LoadStage::Loaded(SystemTime::now(), img.clone()));
+			}
+			
+			// println!("Async loaded {}", pathStr);
+			
+			if !img.isDefault() {
+				if checkID(&global, slot, workId) { return; }
+				let _ = send.send(FileLoaderAction::UpdateFile(LoadedIcon {
+					image: img,
+					path: pathStr,
+				}));
+			}
+		});
+	}
+	
+	while !toScan.is_empty() {
+		sleep(Duration::from_millis(2));
+		// Superseded loads drop their cache entries, which would never finish loading
+		if checkID(&global, slot, workId) { return; }
+		
+		toScan.retain(|f| {
+			match global.clone().write().unwrap().iconCache.get_mut(f) {
+				None => {}
+				Some(stage) => {
+					sendStage(send, f.clone(), stage);
+					if let LoadStage::Loaded(_, _) = stage {
+						return false;
 					}
 				}
-				true
-			});
-		}
-		let _ = send.send(FileLoaderAction::End);
-	});
+			}
+			true
+		});
+	}
 }
 
 fn sendStage(send: &Sender<FileLoaderAction>, pathStr: String, stage: &mut LoadStage<Arc<RgbImg>>) {
@@ -184,24 +245,70 @@ fn loadFromPath(state: Arc<RwLock<GlobalIcons>>, path: PathBuf) -> Arc<RgbImg> {
 	if path.extension().and_then(|s| s.to_str()).filter(|s|
 		["PNG", "JPEG","JPG", "GIF", "BMP", "ICO", "TIFF", "WebP", "AVIF", "PNM", "DDS", "TGA"].contains(&s.to_uppercase().as_str())
 	).is_some() {
-		icon = RgbImg::read(path.as_os_str().to_str().unwrap()).ok();
+		icon = RgbImg::readThumbnail(path.as_os_str().to_str().unwrap()).ok();
 	}
 	
 	icon.map(Arc::new)
 	    .unwrap_or_else(|| state.read().unwrap().default.clone())
 }
 
-fn checkID(global: Arc<RwLock<GlobalIcons>>, currentId: WorkID, send: Sender<FileLoaderAction>) -> bool {
-	let workerId = { global.read().unwrap().workerId };
+fn checkID(global: &Arc<RwLock<GlobalIcons>>, slot: IconSlot, currentId: WorkID) -> bool {
+	let workerId = { global.read().unwrap().workerIds.get(&slot).copied() };
+	workerId != Some(currentId)
+}
+
+
+enum AnimationStage {
+	Loading(Receiver<Result<Vec<(RgbImg, Duration)>, String>>),
+	Ready(Rc<ImageSequence>),
+	Failed,
+}
+
+/// The frames of animated thumbnails, decoded the first time the pointer rests on one. Lives on the UI thread of a window.
+#[derive(Default)]
+pub struct AnimatedThumbnails {
+	entries: HashMap<String, (Instant, AnimationStage)>,
+}
+
+impl AnimatedThumbnails {
+	/// The frame due at the time, None while the frames are loading or could not be
+	pub fn frame(&mut self, path: &str, time: Duration) -> Option<Image> {
+		if !self.entries.contains_key(path) {
+			let (send, receiver) = channel();
+			let pathStr = path.to_string();
+			work::execute(move || {
+				let _ = send.send(RgbImg::readFrames(&pathStr, Some(256), ANIMATION_FRAMES, ANIMATION_BYTES));
+			});
+			self.entries.insert(path.to_string(), (Instant::now(), AnimationStage::Loading(receiver)));
+			self.evict();
+		}
+		
+		let (used, stage) = self.entries.get_mut(path).unwrap();
+		*used = Instant::now();
+		if let AnimationStage::Loading(receiver) = stage {
+			match receiver.try_recv() {
+				Ok(Ok(frames)) => { *stage = AnimationStage::Ready(Rc::new(ImageSequence::fromFrames(frames))); }
+				Ok(Err(_)) | Err(TryRecvError::Disconnected) => { *stage = AnimationStage::Failed; }
+				Err(TryRecvError::Empty) => {}
+			}
+		}
+		match stage {
+			AnimationStage::Ready(sequence) => { Some(sequence.getFrame(time).clone()) }
+			_ => { None }
+		}
+	}
 	
-	if workerId != currentId {
-		let _ = send.send(FileLoaderAction::End);
-		return true;
+	fn evict(&mut self) {
+		while self.entries.len() > ANIMATIONS_KEPT {
+			let oldest = self.entries.iter().min_by_key(|(_, (used, _))| *used).map(|(k, _)| k.clone());
+			match oldest {
+				Some(k) => { self.entries.remove(&k); }
+				None => { break; }
+			}
+		}
 	}
-	false
 }
 
-
 pub fn startIconGC(state: Arc<RwLock<GlobalIcons>>) {
 	thread::spawn(move || {
 		let mut rng = rand::thread_rng();
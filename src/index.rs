@@ -0,0 +1,583 @@
+use std::{fs, mem, thread};
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::RwLock;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use lazy_static::lazy_static;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::config;
+use crate::config::Settings;
+
+lazy_static! {
+	static ref INDEX: RwLock<Option<Index>> = RwLock::new(None);
+}
+
+const MAGIC: &[u8; 8] = b"RXINDEX1";
+const NO_PARENT: u32 = u32::MAX;
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Entry {
+	parent: u32,
+	/// Roots store their full path
+	name: Box<str>,
+	isDir: bool,
+	removed: bool,
+	size: u64,
+	/// Seconds since the unix epoch
+	modified: i64,
+}
+
+impl Entry {
+	fn bytes(&self) -> usize {
+		size_of::<Entry>() + self.name.len() + size_of::<u32>()
+	}
+}
+
+/// Every path below the roots, stored as a tree of names. Removed entries are only marked until they make up a good part of the index, then they are dropped.
+pub struct Index {
+	entries: Vec<Entry>,
+	roots: Vec<u32>,
+	children: HashMap<u32, Vec<u32>>,
+	/// Of the entries that are not removed
+	bytes: usize,
+	/// How many entries are marked as removed
+	removed: usize,
+	maxBytes: usize,
+	/// The memory limit was hit, so the index is missing entries
+	pub truncated: bool,
+	dirty: bool,
+	excludes: GlobSet,
+}
+
+impl Index {
+	fn new(excludes: GlobSet, maxBytes: usize) -> Self {
+		Self {
+			entries: vec![],
+			roots: vec![],
+			children: Default::default(),
+			bytes: 0,
+			removed: 0,
+			maxBytes,
+			truncated: false,
+			dirty: false,
+			excludes,
+		}
+	}
+	
+	fn build(roots: &[PathBuf], excludes: GlobSet, maxBytes: usize) -> Self {
+		let mut index = Self::new(excludes, maxBytes);
+		for root in roots {
+			let meta = match fs::metadata(root) {
+				Ok(m) => { m }
+				Err(err) => {
+					println!("Can not index {}: {}", root.display(), err);
+					continue;
+				}
+			};
+			if let Some(id) = index.push(NO_PARENT, root.to_string_lossy().as_ref(), &meta) {
+				index.roots.push(id);
+				index.addTree(root, id);
+			}
+		}
+		index.dirty = true;
+		index
+	}
+	
+	fn push(&mut self, parent: u32, name: &str, meta: &Metadata) -> Option<u32> {
+		let entry = Entry {
+			parent,
+			name: name.into(),
+			isDir: meta.is_dir(),
+			removed: false,
+			size: if meta.is_dir() { 0 } else { meta.len() },
+			modified: modifiedSecs(meta),
+		};
+		if self.bytes + entry.bytes() > self.maxBytes {
+			self.truncated = true;
+			return None;
+		}
+		
+		let id = self.entries.len() as u32;
+		self.bytes += entry.bytes();
+		self.entries.push(entry);
+		if parent != NO_PARENT {
+			self.children.entry(parent).or_default().push(id);
+		}
+		self.dirty = true;
+		Some(id)
+	}
+	
+	/// Adds everything below dir, symlinked directories are not followed
+	fn addTree(&mut self, dir: &Path, id: u32) {
+		let mut stack = vec![(dir.to_path_buf(), id)];
+		while let Some((dir, id)) = stack.pop() {
+			let read = match fs::read_dir(&dir) {
+				Ok(r) => { r }
+				Err(_) => { continue; }
+			};
+			for entry in read.flatten() {
+				let path = entry.path();
+				if self.isExcluded(&path) {
+					continue;
+				}
+				let meta = match entry.metadata() {
+					Ok(m) => { m }
+					Err(_) => { continue; }
+				};
+				let child = match self.push(id, &entry.file_name().to_string_lossy(), &meta) {
+					None => { return; }
+					Some(c) => { c }
+				};
+				if meta.is_dir() {
+					stack.push((path, child));
+				}
+			}
+		}
+	}
+	
+	fn isExcluded(&self, path: &Path) -> bool {
+		!self.excludes.is_empty() &&
+			(path.file_name().map(|n| self.excludes.is_match(n)).unwrap_or(false) || self.excludes.is_match(path))
+	}
+	
+	fn fullPath(&self, mut id: u32) -> PathBuf {
+		let mut names = vec![];
+		while id != NO_PARENT {
+			let e = &self.entries[id as usize];
+			names.push(&*e.name);
+			id = e.parent;
+		}
+		names.iter().rev().collect()
+	}
+	
+	fn find(&self, path: &Path) -> Option<u32> {
+		for root in &self.roots {
+			let rest = match path.strip_prefix(&*self.entries[*root as usize].name) {
+				Ok(r) => { r }
+				Err(_) => { continue; }
+			};
+			let mut id = *root;
+			for name in rest.iter() {
+				id = *self.children.get(&id)?.iter().find(|c| *self.entries[**c as usize].name == *name)?;
+			}
+			return Some(id);
+		}
+		None
+	}
+	
+	fn remove(&mut self, id: u32) {
+		let parent = self.entries[id as usize].parent;
+		if let Some(siblings) = self.children.get_mut(&parent) {
+			siblings.retain(|c| *c != id);
+		}
+		let mut stack = vec![id];
+		while let Some(id) = stack.pop() {
+			let e = &mut self.entries[id as usize];
+			e.removed = true;
+			self.bytes -= e.bytes();
+			self.removed += 1;
+			if let Some(children) = self.children.remove(&id) {
+				stack.extend(children);
+			}
+		}
+		self.dirty = true;
+	}
+	
+	/// Drops the removed entries once they are a quarter of all, which gives the remaining ones new ids
+	fn compactIfSparse(&mut self) {
+		if self.removed < 4096 || self.removed * 4 < self.entries.len() {
+			return;
+		}
+		let mut newIds = vec![NO_PARENT; self.entries.len()];
+		let mut entries = Vec::with_capacity(self.entries.len() - self.removed);
+		self.children.clear();
+		for (id, e) in mem::take(&mut self.entries).into_iter().enumerate() {
+			if e.removed { continue; }
+			// Parents always come before their children
+			let newId = entries.len() as u32;
+			newIds[id] = newId;
+			let parent = if e.parent == NO_PARENT { NO_PARENT } else { newIds[e.parent as usize] };
+			if parent != NO_PARENT {
+				self.children.entry(parent).or_default().push(newId);
+			}
+			entries.push(Entry { parent, ..e });
+		}
+		self.roots = self.roots.iter().map(|r| newIds[*r as usize]).collect();
+		self.entries = entries;
+		self.removed = 0;
+	}
+	
+	/// Brings a loaded index in line with the disk. Only directories whose modification time changed are read again,
+	/// so files that were changed in place while nobody watched keep their old size and date until they change again.
+	fn reconcile(&mut self) {
+		let mut stack = self.roots.clone();
+		while let Some(id) = stack.pop() {
+			let path = self.fullPath(id);
+			let isRoot = self.entries[id as usize].parent == NO_PARENT;
+			let meta = if isRoot { fs::metadata(&path) } else { fs::symlink_metadata(&path) };
+			let meta = match meta {
+				Ok(m) if m.is_dir() => { m }
+				_ => {
+					// Gone or no longer a directory, roots are kept so they are found again once they are back
+					let children = self.children.get(&id).cloned().unwrap_or_default();
+					if isRoot {
+						children.into_iter().for_each(|c| self.remove(c));
+					} else {
+						self.remove(id);
+						self.refresh(&path);
+					}
+					continue;
+				}
+			};
+			
+			let modified = modifiedSecs(&meta);
+			if self.entries[id as usize].modified != modified {
+				self.entries[id as usize].modified = modified;
+				self.dirty = true;
+				if !self.rereadDir(&path, id) {
+					return;
+				}
+			}
+			if let Some(children) = self.children.get(&id) {
+				stack.extend(children.iter().filter(|c| self.entries[**c as usize].isDir));
+			}
+		}
+	}
+	
+	/// Adds and removes the children of dir to match the disk. False when the memory limit was hit.
+	fn rereadDir(&mut self, dir: &Path, id: u32) -> bool {
+		let mut onDisk = HashMap::new();
+		if let Ok(read) = fs::read_dir(dir) {
+			for entry in read.flatten() {
+				if self.isExcluded(&entry.path()) {
+					continue;
+				}
+				if let Ok(meta) = entry.metadata() {
+					onDisk.insert(entry.file_name().to_string_lossy().into_owned(), meta);
+				}
+			}
+		}
+		
+		for child in self.children.get(&id).cloned().unwrap_or_default() {
+			let e = &mut self.entries[child as usize];
+			match onDisk.remove(&*e.name) {
+				Some(meta) if meta.is_dir() == e.isDir => {
+					// Directories get their date in reconcile, so their content is checked too
+					if !e.isDir {
+						e.size = meta.len();
+						e.modified = modifiedSecs(&meta);
+					}
+				}
+				Some(meta) => {
+					let name = e.name.to_string();
+					self.remove(child);
+					onDisk.insert(name, meta);
+				}
+				None => { self.remove(child); }
+			}
+		}
+		
+		for (name, meta) in onDisk {
+			let child = match self.push(id, &name, &meta) {
+				None => { return false; }
+				Some(c) => { c }
+			};
+			if meta.is_dir() {
+				self.addTree(&dir.join(&name), child);
+			}
+		}
+		!self.truncated
+	}
+	
+	/// Brings the entry for path in line with the disk
+	fn refresh(&mut self, path: &Path) {
+		let meta = match fs::symlink_metadata(path) {
+			Ok(m) => { m }
+			Err(_) => {
+				if let Some(id) = self.find(path) {
+					self.remove(id);
+				}
+				return;
+			}
+		};
+		
+		match self.find(path) {
+			Some(id) => {
+				let e = &mut self.entries[id as usize];
+				e.size = if meta.is_dir() { 0 } else { meta.len() };
+				e.modified = modifiedSecs(&meta);
+				self.dirty = true;
+			}
+			None => {
+				if self.isExcluded(path) {
+					return;
+				}
+				let (parent, name) = match (path.parent().and_then(|p| self.find(p)), path.file_name()) {
+					(Some(p), Some(n)) => { (p, n) }
+					_ => { return; }
+				};
+				if let Some(id) = self.push(parent, &name.to_string_lossy(), &meta) {
+					if meta.is_dir() {
+						self.addTree(path, id);
+					}
+				}
+			}
+		}
+	}
+	
+	fn covers(&self, path: &Path) -> bool {
+		self.roots.iter().any(|r| path.starts_with(&*self.entries[*r as usize].name))
+	}
+	
+	fn rootPaths(&self) -> Vec<PathBuf> {
+		self.roots.iter().map(|r| PathBuf::from(&*self.entries[*r as usize].name)).collect()
+	}
+	
+	/// Only the entries that are still there are written
+	fn save(&self, path: &Path) -> Result<(), String> {
+		let tmp = path.with_extension("tmp");
+		let mut out = BufWriter::new(File::create(&tmp).map_err(|err| err.to_string())?);
+		
+		let mut newIds = vec![NO_PARENT; self.entries.len()];
+		let mut data = Vec::with_capacity(self.bytes);
+		let mut count: u32 = 0;
+		for (id, e) in self.entries.iter().enumerate() {
+			if e.removed { continue; }
+			// Parents always come before their children
+			let parent = if e.parent == NO_PARENT { NO_PARENT } else { newIds[e.parent as usize] };
+			newIds[id] = count;
+			count += 1;
+			
+			let name = e.name.as_bytes();
+			data.extend_from_slice(&parent.to_le_bytes());
+			data.push(e.isDir as u8);
+			data.extend_from_slice(&e.size.to_le_bytes());
+			data.extend_from_slice(&e.modified.to_le_bytes());
+			data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+			data.extend_from_slice(name);
+		}
+		
+		out.write_all(MAGIC).map_err(|err| err.to_string())?;
+		out.write_all(&[self.truncated as u8]).map_err(|err| err.to_string())?;
+		out.write_all(&count.to_le_bytes()).map_err(|err| err.to_string())?;
+		out.write_all(&data).map_err(|err| err.to_string())?;
+		out.flush().map_err(|err| err.to_string())?;
+		drop(out);
+		fs::rename(&tmp, path).map_err(|err| err.to_string())
+	}
+	
+	fn load(path: &Path, excludes: GlobSet, maxBytes: usize) -> Result<Self, String> {
+		let mut data = vec![];
+		BufReader::new(File::open(path).map_err(|err| err.to_string())?).read_to_end(&mut data).map_err(|err| err.to_string())?;
+		
+		let mut r = ByteReader { data: &data, pos: 0 };
+		if r.take(MAGIC.len())? != MAGIC {
+			return Err("Not an index file".to_string());
+		}
+		let mut index = Self::new(excludes, maxBytes);
+		index.truncated = r.take(1)?[0] != 0;
+		let count = r.u32()?;
+		
+		for id in 0..count {
+			let parent = r.u32()?;
+			let isDir = r.take(1)?[0] != 0;
+			let size = r.u64()?;
+			let modified = r.u64()? as i64;
+			let len = r.u32()? as usize;
+			let name = String::from_utf8_lossy(r.take(len)?).into_owned().into_boxed_str();
+			
+			if parent == NO_PARENT {
+				index.roots.push(id);
+			} else if parent >= id {
+				return Err("Index file is corrupted".to_string());
+			} else {
+				index.children.entry(parent).or_default().push(id);
+			}
+			let entry = Entry { parent, name, isDir, removed: false, size, modified };
+			index.bytes += entry.bytes();
+			index.entries.push(entry);
+		}
+		Ok(index)
+	}
+}
+
+struct ByteReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+	fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+		let res = self.data.get(self.pos..self.pos + len).ok_or("Index file is truncated")?;
+		self.pos += len;
+		Ok(res)
+	}
+	
+	fn u32(&mut self) -> Result<u32, String> {
+		Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+	}
+	
+	fn u64(&mut self) -> Result<u64, String> {
+		Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+	}
+}
+
+fn modifiedSecs(meta: &Metadata) -> i64 {
+	meta.modified().ok()
+	    .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+	    .map(|d| d.as_secs() as i64)
+	    .unwrap_or(0)
+}
+
+pub struct IndexEntry<'a> {
+	pub name: &'a str,
+	pub isDir: bool,
+	pub size: u64,
+	/// Seconds since the unix epoch
+	pub modified: i64,
+}
+
+/// Paths below root whose entry passes accept. None when the index can't answer for root, then the disk has to be searched.
+pub fn query<F: FnMut(&IndexEntry) -> bool>(root: &Path, mut accept: F) -> Option<(Vec<PathBuf>, usize)> {
+	let index = INDEX.read().unwrap();
+	let index = index.as_ref()?;
+	if index.truncated || !index.covers(root) {
+		return None;
+	}
+	
+	let mut res = vec![];
+	let mut scanned = 0;
+	for (id, e) in index.entries.iter().enumerate() {
+		if e.removed || e.parent == NO_PARENT { continue; }
+		scanned += 1;
+		let entry = IndexEntry { name: &e.name, isDir: e.isDir, size: e.size, modified: e.modified };
+		if !accept(&entry) { continue; }
+		
+		let path = index.fullPath(id as u32);
+		if path.starts_with(root) && path != root {
+			res.push(path);
+		}
+	}
+	Some((res, scanned))
+}
+
+/// Loads the last index and keeps it up to date in the background, if enabled in the settings
+pub fn start(settings: &Settings) {
+	if !settings.indexEnabled {
+		return;
+	}
+	
+	let mut roots = settings.indexRoots.clone();
+	if roots.is_empty() {
+		roots.extend(home::home_dir());
+	}
+	let mut excludes = GlobSetBuilder::new();
+	for pattern in &settings.indexExcludes {
+		match Glob::new(pattern) {
+			Ok(g) => { excludes.add(g); }
+			Err(err) => { println!("Invalid index exclude pattern {}: {}", pattern, err); }
+		}
+	}
+	let excludes = excludes.build().unwrap_or_else(|_| GlobSet::empty());
+	let maxBytes = match settings.indexMaxMemoryMB {
+		0 => { 256 }
+		mb => { mb }
+	} as usize * 1024 * 1024;
+	
+	let res = thread::Builder::new().name("Indexer".into()).spawn(move || {
+		run(roots, excludes, maxBytes);
+	});
+	if let Err(err) = res {
+		println!("Failed to start indexer: {}", err);
+	}
+}
+
+fn run(roots: Vec<PathBuf>, excludes: GlobSet, maxBytes: usize) {
+	let file = config::cacheFile("index.bin");
+	let start = Instant::now();
+	
+	// A truncated index is missing entries nobody knows about, so it is built again
+	let loaded = match Index::load(&file, excludes.clone(), maxBytes) {
+		Ok(index) if index.rootPaths() == roots && !index.truncated => {
+			println!("Index loaded in {:?}", start.elapsed());
+			Some(index)
+		}
+		Ok(_) => { None }
+		Err(err) => {
+			println!("No usable index in {}: {}", file.display(), err);
+			None
+		}
+	};
+	
+	// Watching starts before the scan so nothing that changes during it is missed
+	let (send, events) = channel();
+	let mut watcher = match notify::recommended_watcher(send) {
+		Ok(w) => { w }
+		Err(err) => {
+			println!("Failed to watch for changes, the index will not be updated: {}", err);
+			return;
+		}
+	};
+	for root in &roots {
+		if let Err(err) = watcher.watch(root, RecursiveMode::Recursive) {
+			println!("Failed to watch {}: {}", root.display(), err);
+		}
+	}
+	
+	let index = match loaded {
+		Some(mut index) => {
+			index.reconcile();
+			index.compactIfSparse();
+			println!("Index brought up to date in {:?}", start.elapsed());
+			index
+		}
+		None => {
+			let index = Index::build(&roots, excludes, maxBytes);
+			println!("Indexed {} entries in {:?}", index.entries.len(), start.elapsed());
+			index
+		}
+	};
+	if index.truncated {
+		println!("The index reached its memory limit");
+	}
+	*INDEX.write().unwrap() = Some(index);
+	
+	let mut lastSave = None;
+	loop {
+		match events.recv_timeout(SAVE_INTERVAL) {
+			Ok(Ok(event)) => {
+				if !matches!(event.kind, EventKind::Access(_)) {
+					let mut index = INDEX.write().unwrap();
+					if let Some(index) = index.as_mut() {
+						for path in &event.paths {
+							index.refresh(path);
+						}
+						index.compactIfSparse();
+					}
+				}
+			}
+			Ok(Err(err)) => { println!("Index watcher error: {}", err); }
+			Err(RecvTimeoutError::Timeout) => {}
+			Err(RecvTimeoutError::Disconnected) => { return; }
+		}
+		
+		if lastSave.map(|t: Instant| t.elapsed() < SAVE_INTERVAL).unwrap_or(false) {
+			continue;
+		}
+		let mut index = INDEX.write().unwrap();
+		if let Some(index) = index.as_mut().filter(|i| i.dirty) {
+			if let Err(err) = index.save(&file) {
+				println!("Failed to save index to {}: {}", file.display(), err);
+			}
+			index.dirty = false;
+		}
+		lastSave = Some(Instant::now());
+	}
+}
@@ -0,0 +1,168 @@
+use std::{env, fs, io, thread};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use crate::cli::Args;
+
+/// One socket per config directory, so instances that don't share settings also don't share windows
+#[cfg(unix)]
+fn socketPath(args: &Args) -> Result<PathBuf, String> {
+	let name = match &args.configDir {
+		None => { "rexplorer.sock".to_string() }
+		Some(config) => {
+			let mut hasher = DefaultHasher::new();
+			config.hash(&mut hasher);
+			format!("rexplorer-{:x}.sock", hasher.finish())
+		}
+	};
+	Ok(socketDir()?.join(name))
+}
+
+/// A directory only this user can enter, so nobody else can put a socket where launches look for one
+#[cfg(unix)]
+fn socketDir() -> Result<PathBuf, String> {
+	let uid = unsafe { libc::getuid() };
+	let dir = match env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).filter(|p| p.is_absolute()) {
+		Some(runtime) => { runtime.join("rexplorer") }
+		None => { env::temp_dir().join(format!("rexplorer-{uid}")) }
+	};
+	match fs::DirBuilder::new().mode(0o700).create(&dir) {
+		Ok(()) => {}
+		Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+		Err(err) => { return Err(format!("{}: {}", dir.display(), err)); }
+	}
+	
+	// It may have been there before, made by someone else
+	let meta = fs::symlink_metadata(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+	if !meta.is_dir() || meta.uid() != uid || meta.permissions().mode() & 0o077 != 0 {
+		return Err(format!("{} is not a private directory of this user", dir.display()));
+	}
+	Ok(dir)
+}
+
+/// Hands the arguments to an already running instance. Ok means the other instance took over and this one should exit.
+#[cfg(unix)]
+pub fn forward(args: &Args) -> Result<(), String> {
+	let path = socketPath(args)?;
+	let mut stream = UnixStream::connect(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+	stream.set_read_timeout(Some(Duration::from_secs(5))).map_err(|err| err.to_string())?;
+	
+	let msg = serde_json::to_string(args).map_err(|err| err.to_string())?;
+	stream.write_all(msg.as_bytes()).map_err(|err| err.to_string())?;
+	stream.write_all(b"\n").map_err(|err| err.to_string())?;
+	
+	let mut ack = String::new();
+	BufReader::new(stream).read_line(&mut ack).map_err(|err| err.to_string())?;
+	if ack.trim() != "ok" {
+		return Err(format!("Unexpected response \"{}\"", ack.trim()));
+	}
+	Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn forward(_args: &Args) -> Result<(), String> {
+	Err("Single instance is only supported on unix".to_string())
+}
+
+/// Starts accepting arguments from later launches. Every received launch is passed on to send.
+/// False when this instance is not listening, then it must not clean up the socket either.
+#[cfg(unix)]
+pub fn listen(args: &Args, send: Sender<Args>) -> bool {
+	let path = match socketPath(args) {
+		Ok(p) => { p }
+		Err(err) => {
+			println!("Failed to listen: {}", err);
+			return false;
+		}
+	};
+	
+	let listener = match UnixListener::bind(&path) {
+		Ok(l) => { l }
+		Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
+			// Only a socket nobody listens on any more is taken over, a live one may belong to an instance started at the same time
+			match UnixStream::connect(&path) {
+				Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {}
+				_ => {
+					println!("Another instance is listening on {}", path.display());
+					return false;
+				}
+			}
+			let _ = fs::remove_file(&path);
+			match UnixListener::bind(&path) {
+				Ok(l) => { l }
+				Err(err) => {
+					println!("Failed to listen on {}: {}", path.display(), err);
+					return false;
+				}
+			}
+		}
+		Err(err) => {
+			println!("Failed to listen on {}: {}", path.display(), err);
+			return false;
+		}
+	};
+	
+	thread::Builder::new().name("IPC".into()).spawn(move || {
+		for stream in listener.incoming() {
+			match stream {
+				Ok(stream) => {
+					// A client that never sends its line must not hold up the launches after it
+					let send = send.clone();
+					let res = thread::Builder::new().name("IPC client".into()).spawn(move || handleClient(stream, &send));
+					if let Err(err) = res {
+						println!("Failed to handle IPC connection: {}", err);
+					}
+				}
+				Err(err) => { println!("IPC connection failed: {}", err); }
+			}
+		}
+	}).is_ok()
+}
+
+#[cfg(not(unix))]
+pub fn listen(_args: &Args, _send: Sender<Args>) -> bool {
+	false
+}
+
+#[cfg(unix)]
+fn handleClient(stream: UnixStream, send: &Sender<Args>) {
+	let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+	let mut reader = BufReader::new(&stream);
+	
+	let mut line = String::new();
+	if let Err(err) = reader.read_line(&mut line) {
+		println!("IPC read failed: {}", err);
+		return;
+	}
+	
+	let res = match serde_json::from_str::<Args>(&line) {
+		Ok(args) => {
+			let _ = send.send(args);
+			"ok"
+		}
+		Err(err) => {
+			println!("IPC message malformed: {}", err);
+			"malformed"
+		}
+	};
+	let _ = (&stream).write_all(format!("{res}\n").as_bytes());
+}
+
+#[cfg(unix)]
+pub fn cleanup(args: &Args) {
+	if let Ok(path) = socketPath(args) {
+		let _ = fs::remove_file(path);
+	}
+}
+
+#[cfg(not(unix))]
+pub fn cleanup(_args: &Args) {}
@@ -0,0 +1,353 @@
+use std::fs;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use lazy_static::lazy_static;
+
+use crate::config::dataFile;
+use crate::fileops::{copyTree, moveTo, removeTree, symlink};
+use crate::{bulkrename, trash};
+
+lazy_static! {
+	static ref JOURNAL: Mutex<Option<Journal>> = Mutex::new(None);
+}
+
+/// Older entries are forgotten
+const MAX_ENTRIES: usize = 100;
+
+/// A file operation that was done and how to find the files it touched
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug, Clone)]
+pub enum Entry {
+	/// Renamed inside their folder, old and new path. Undone all at once so names can be swapped.
+	Renamed(Vec<(PathBuf, PathBuf)>),
+	/// Moved to another folder, from where to where
+	Moved(Vec<(PathBuf, PathBuf)>),
+	/// The source, the copy and when the copy was last modified, to notice when it was changed since
+	Copied(Vec<(PathBuf, PathBuf, Option<SystemTime>)>),
+	/// What the links point to and the links
+	Linked(Vec<(PathBuf, PathBuf)>),
+	/// Where the files were and where they are in the trash
+	Trashed(Vec<(PathBuf, PathBuf)>),
+	/// A new folder or file, only removed again while it is empty
+	Created { path: PathBuf, isDir: bool },
+}
+
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Debug, Default)]
+struct Journal {
+	undo: Vec<Entry>,
+	redo: Vec<Entry>,
+}
+
+impl Entry {
+	/// Remembers when the copies were made, so undo does not delete copies that were edited since
+	pub fn copied(done: Vec<(PathBuf, PathBuf)>) -> Self {
+		Entry::Copied(done.into_iter().map(|(src, copy)| {
+			let modified = treeModified(&copy);
+			(src, copy, modified)
+		}).collect())
+	}
+	
+	fn isEmpty(&self) -> bool {
+		match self {
+			Entry::Renamed(v) | Entry::Moved(v) | Entry::Linked(v) | Entry::Trashed(v) => { v.is_empty() }
+			Entry::Copied(c) => { c.is_empty() }
+			Entry::Created { .. } => { false }
+		}
+	}
+	
+	fn describe(&self) -> String {
+		let items = |n: usize| if n == 1 { "1 item".to_string() } else { format!("{} items", n) };
+		match self {
+			Entry::Renamed(r) => { format!("renaming {}", items(r.len())) }
+			Entry::Moved(m) => { format!("moving {}", items(m.len())) }
+			Entry::Copied(c) => { format!("copying {}", items(c.len())) }
+			Entry::Linked(l) => { format!("linking {}", items(l.len())) }
+			Entry::Trashed(t) => { format!("trashing {}", items(t.len())) }
+			Entry::Created { path, .. } => { format!("creating {}", path.display()) }
+		}
+	}
+	
+	/// Reverses the operation and returns what redoes it
+	fn undo(self) -> Result<Entry, Failure> {
+		match self {
+			Entry::Renamed(renames) => {
+				bulkrename::checkRevert(&renames)?;
+				// Nothing is renamed when that fails, so all of it can be tried again
+				bulkrename::revert(&renames).map_err(|err| Failure::partial(err, None, Some(Entry::Renamed(renames.clone()))))?;
+				Ok(Entry::Renamed(renames))
+			}
+			Entry::Moved(moves) => {
+				let back: Vec<(PathBuf, PathBuf)> = moves.iter().map(|(from, to)| (to.clone(), from.clone())).collect();
+				checkMoves(&back)?;
+				moveAll(&back).map_err(|(err, done)| {
+					Failure::partial(err, Some(Entry::Moved(moves[..done].to_vec())), Some(Entry::Moved(moves[done..].to_vec())))
+				})?;
+				Ok(Entry::Moved(moves))
+			}
+			Entry::Copied(copies) => {
+				for (_, copy, when) in &copies {
+					if fs::symlink_metadata(copy).is_err() {
+						return Err(format!("{} is gone", copy.display()).into());
+					}
+					// Without the time of the copy, an edited copy can't be told apart from an untouched one
+					if when.is_none() {
+						return Err(format!("Can not tell whether {} was changed since, so it is kept", copy.display()).into());
+					}
+					if treeModified(copy) != *when {
+						return Err(format!("{} was changed since", copy.display()).into());
+					}
+				}
+				for (i, (_, copy, _)) in copies.iter().enumerate() {
+					if let Err(err) = removeTree(copy) {
+						let done = copies[..i].iter().map(|(src, copy, _)| (src.clone(), copy.clone(), None)).collect();
+						let err = format!("Failed to delete {}: {}", copy.display(), err);
+						return Err(Failure::partial(err, Some(Entry::Copied(done)), Some(Entry::Copied(copies[i..].to_vec()))));
+					}
+				}
+				Ok(Entry::Copied(copies))
+			}
+			Entry::Linked(links) => {
+				for (target, link) in &links {
+					if fs::read_link(link).ok().as_ref() != Some(target) {
+						return Err(format!("{} is not the link that was made anymore", link.display()).into());
+					}
+				}
+				for (i, (_, link)) in links.iter().enumerate() {
+					if let Err(err) = fs::remove_file(link) {
+						let err = format!("Failed to delete {}: {}", link.display(), err);
+						return Err(Failure::partial(err, Some(Entry::Linked(links[..i].to_vec())), Some(Entry::Linked(links[i..].to_vec()))));
+					}
+				}
+				Ok(Entry::Linked(links))
+			}
+			Entry::Trashed(trashed) => {
+				for (original, inTrash) in &trashed {
+					if fs::symlink_metadata(inTrash).is_err() {
+						return Err(format!("{} is not in the trash anymore", original.display()).into());
+					}
+					if fs::symlink_metadata(original).is_ok() {
+						return Err(format!("{} exists again", original.display()).into());
+					}
+				}
+				for (i, (original, inTrash)) in trashed.iter().enumerate() {
+					if let Err(err) = trash::restore(inTrash, original) {
+						return Err(Failure::partial(err, Some(Entry::Trashed(trashed[..i].to_vec())), Some(Entry::Trashed(trashed[i..].to_vec()))));
+					}
+				}
+				Ok(Entry::Trashed(trashed))
+			}
+			Entry::Created { path, isDir } => {
+				let empty = if isDir {
+					fs::read_dir(&path).map(|mut r| r.next().is_none()).map_err(|_| format!("{} is gone", path.display()))?
+				} else {
+					fs::metadata(&path).map(|m| m.len() == 0).map_err(|_| format!("{} is gone", path.display()))?
+				};
+				if !empty {
+					return Err(format!("{} is not empty anymore", path.display()).into());
+				}
+				removeTree(&path).map_err(|err| format!("Failed to delete {}: {}", path.display(), err))?;
+				Ok(Entry::Created { path, isDir })
+			}
+		}
+	}
+	
+	/// Does the operation again and returns what undoes it, which can differ from before, like where the files end up in the trash
+	fn redo(self) -> Result<Entry, Failure> {
+		match self {
+			Entry::Renamed(renames) => {
+				let swapped: Vec<(PathBuf, PathBuf)> = renames.iter().map(|(old, new)| (new.clone(), old.clone())).collect();
+				bulkrename::checkRevert(&swapped)?;
+				bulkrename::revert(&swapped).map_err(|err| Failure::partial(err, None, Some(Entry::Renamed(renames.clone()))))?;
+				Ok(Entry::Renamed(renames))
+			}
+			Entry::Moved(moves) => {
+				checkMoves(&moves)?;
+				moveAll(&moves).map_err(|(err, done)| {
+					Failure::partial(err, Some(Entry::Moved(moves[..done].to_vec())), Some(Entry::Moved(moves[done..].to_vec())))
+				})?;
+				Ok(Entry::Moved(moves))
+			}
+			Entry::Copied(copies) => {
+				checkAll(copies.iter().map(|(src, copy, _)| (src.as_path(), copy.as_path())), &HashSet::new())?;
+				let mut done = vec![];
+				for (i, (src, copy, _)) in copies.iter().enumerate() {
+					if let Err(err) = copyTree(src, copy) {
+						let err = format!("Failed to copy {}: {}", src.display(), err);
+						return Err(Failure::partial(err, Some(Entry::copied(done)), Some(Entry::Copied(copies[i..].to_vec()))));
+					}
+					done.push((src.clone(), copy.clone()));
+				}
+				Ok(Entry::copied(done))
+			}
+			Entry::Linked(links) => {
+				for (_, link) in &links {
+					if fs::symlink_metadata(link).is_ok() {
+						return Err(format!("{} exists again", link.display()).into());
+					}
+				}
+				for (i, (target, link)) in links.iter().enumerate() {
+					if let Err(err) = symlink(target, link) {
+						let err = format!("Failed to link {}: {}", link.display(), err);
+						return Err(Failure::partial(err, Some(Entry::Linked(links[..i].to_vec())), Some(Entry::Linked(links[i..].to_vec()))));
+					}
+				}
+				Ok(Entry::Linked(links))
+			}
+			Entry::Trashed(trashed) => {
+				for (original, _) in &trashed {
+					if fs::symlink_metadata(original).is_err() {
+						return Err(format!("{} is gone", original.display()).into());
+					}
+				}
+				let mut done = vec![];
+				for (i, (original, _)) in trashed.iter().enumerate() {
+					match trash::trash(original) {
+						Ok(inTrash) => { done.push((original.clone(), inTrash)); }
+						Err(err) => { return Err(Failure::partial(err, Some(Entry::Trashed(done)), Some(Entry::Trashed(trashed[i..].to_vec())))); }
+					}
+				}
+				Ok(Entry::Trashed(done))
+			}
+			Entry::Created { path, isDir } => {
+				if fs::symlink_metadata(&path).is_ok() {
+					return Err(format!("{} exists again", path.display()).into());
+				}
+				let res = if isDir { fs::create_dir(&path) } else { fs::OpenOptions::new().write(true).create_new(true).open(&path).map(|_| ()) };
+				res.map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+				Ok(Entry::Created { path, isDir })
+			}
+		}
+	}
+}
+
+/// Why an undo or redo failed
+struct Failure {
+	err: String,
+	/// What reverses the part that was done before the failure
+	done: Option<Entry>,
+	/// The part that was not done yet, kept so it can be tried again. None when the files changed so it can't be done anymore.
+	left: Option<Entry>,
+}
+
+impl Failure {
+	/// Empty parts are dropped
+	fn partial(err: String, done: Option<Entry>, left: Option<Entry>) -> Self {
+		Self { err, done: done.filter(|e| !e.isEmpty()), left: left.filter(|e| !e.isEmpty()) }
+	}
+}
+
+impl From<String> for Failure {
+	fn from(err: String) -> Self {
+		Self { err, done: None, left: None }
+	}
+}
+
+/// The latest modification of anything in the tree, so changes deep inside a copied folder are noticed too
+fn treeModified(path: &Path) -> Option<SystemTime> {
+	let meta = fs::symlink_metadata(path).ok()?;
+	let mut latest = meta.modified().ok()?;
+	if meta.is_dir() {
+		for entry in fs::read_dir(path).ok()?.flatten() {
+			latest = latest.max(treeModified(&entry.path())?);
+		}
+	}
+	Some(latest)
+}
+/// Every source has to exist and every target has to be free, unless one of the sources frees it
+fn checkAll<'a>(pairs: impl Iterator<Item=(&'a Path, &'a Path)>, freed: &HashSet<&Path>) -> Result<(), String> {
+	for (from, to) in pairs {
+		if fs::symlink_metadata(from).is_err() {
+			return Err(format!("{} is gone", from.display()));
+		}
+		if !freed.contains(to) && fs::symlink_metadata(to).is_ok() {
+			return Err(format!("{} exists again", to.display()));
+		}
+	}
+	Ok(())
+}
+
+fn checkMoves(moves: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+	let freed = moves.iter().map(|(from, _)| from.as_path()).collect();
+	checkAll(moves.iter().map(|(from, to)| (from.as_path(), to.as_path())), &freed)
+}
+
+/// A failure comes with how many were moved before it
+fn moveAll(moves: &[(PathBuf, PathBuf)]) -> Result<(), (String, usize)> {
+	for (i, (from, to)) in moves.iter().enumerate() {
+		moveTo(from, to).map_err(|err| (format!("Failed to move {}: {}", from.display(), err), i))?;
+	}
+	Ok(())
+}
+
+fn file() -> PathBuf {
+	dataFile("Journal.json")
+}
+
+fn withJournal<R, F: FnOnce(&mut Journal) -> R>(f: F) -> R {
+	let mut journal = JOURNAL.lock().unwrap();
+	let journal = journal.get_or_insert_with(|| {
+		fs::read_to_string(file())
+			.ok()
+			.and_then(|data| serde_json::from_str(&data).map_err(|err| {
+				println!("Journal malformed: {}", err);
+			}).ok())
+			.unwrap_or_default()
+	});
+	let res = f(journal);
+	
+	fs::write(file(), serde_json::to_string(journal).unwrap()).map_err(|err| {
+		println!("Failed to save the journal: {}", err);
+	}).ok();
+	res
+}
+
+/// Something new was done, which makes what was undone before impossible to redo
+pub fn record(entry: Entry) {
+	withJournal(|j| {
+		j.undo.push(entry);
+		if j.undo.len() > MAX_ENTRIES {
+			j.undo.remove(0);
+		}
+		j.redo.clear();
+	});
+}
+
+/// What happened, for the status line. An entry that can not be undone because the files changed is dropped.
+/// When undoing fails partway, what was undone can be redone and the rest stays to be undone.
+pub fn undo() -> Result<String, String> {
+	withJournal(|j| {
+		let entry = j.undo.pop().ok_or("Nothing to undo")?;
+		let what = entry.describe();
+		match entry.undo() {
+			Ok(redo) => {
+				j.redo.push(redo);
+				Ok(format!("Undid {}", what))
+			}
+			Err(failure) => {
+				j.redo.extend(failure.done);
+				j.undo.extend(failure.left);
+				Err(format!("Can not undo {}: {}", what, failure.err))
+			}
+		}
+	})
+}
+
+pub fn redo() -> Result<String, String> {
+	withJournal(|j| {
+		let entry = j.redo.pop().ok_or("Nothing to redo")?;
+		let what = entry.describe();
+		match entry.redo() {
+			Ok(undo) => {
+				j.undo.push(undo);
+				Ok(format!("Redid {}", what))
+			}
+			Err(failure) => {
+				j.undo.extend(failure.done);
+				j.redo.extend(failure.left);
+				Err(format!("Can not redo {}: {}", what, failure.err))
+			}
+		}
+	})
+}
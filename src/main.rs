@@ -3,64 +3,93 @@
 #![allow(unused_imports)]
 #![windows_subsystem = "windows"]
 
-use std::{env, fmt, fs, thread};
+use std::{env, fmt, fs, process, thread};
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::ops::Deref;
 use std::path::{Component, Path, PathBuf};
+use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex, RwLock};
-use std::sync::mpsc::{channel, Receiver};
-use std::time::{Duration, SystemTime};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::{Duration, Instant, SystemTime};
 
+#[cfg(windows)]
 use normpath::PathExt;
-use slint::{Image, Model, ModelRc, PhysicalPosition, PhysicalSize, SharedString, SharedVector, Timer, TimerMode, WindowPosition, WindowSize};
-use slint::platform::SetPlatformError;
+use slint::{Image, Model, ModelRc, PhysicalPosition, PhysicalSize, SharedString, SharedVector, Timer, TimerMode, VecModel, WindowPosition, WindowSize};
+use slint::platform::{SetPlatformError, WindowEvent};
 use slint::private_unstable_api::re_exports::SharedVectorModel;
 
 use config::WindowBox;
 
-use crate::config::WindowInfo;
-use crate::icon::{FileLoaderAction, GlobalIcons};
-use crate::rgba_img::ImageSequence;
+use crate::cli::{Args, ParseResult, Target};
+use crate::config::{IgnoreMode, WindowInfo};
+use crate::directory::DirectoryReader;
+use crate::actions::{Action, Context, Registry};
+use crate::bulkrename::{Batch, Case, Pattern};
+use crate::clipboard::Content;
+use crate::fileops::{OpEvent, OpKind, Operation, RunningOp};
+use crate::journal::Entry;
+use crate::icon::{AnimatedThumbnails, FileLoaderAction, GlobalIcons, IconSlot};
+use crate::matching::{MatchMode, NameMatcher};
+use crate::places::Place;
+use crate::preview::Preview;
+use crate::viewer::Playback;
+use crate::properties::{Change, SizeEvent, Summary};
+use crate::search::{Kind, SearchEvent, SearchFields, SearchQuery};
+use crate::rgba_img::{ImageSequence, RgbImg};
+use crate::visibility::{EntryFilter, Visibility};
 
 mod config;
 mod rgba_img;
 mod work;
 mod icon;
+mod cli;
+mod ipc;
+mod visibility;
+mod directory;
+mod matching;
+mod search;
+mod grep;
+mod index;
+mod uri;
+mod bookmarks;
+mod frecency;
+mod quickjump;
+mod pathinput;
+mod places;
+mod fileops;
+mod clipboard;
+mod bulkrename;
+mod datetime;
+mod trash;
+mod journal;
+mod templates;
+mod actions;
+mod mime;
+mod openwith;
+mod properties;
+mod highlight;
+mod media;
+mod preview;
+mod viewer;
+mod photo;
+mod activate;
+#[cfg(target_os = "linux")]
+mod dbus_service;
 
-slint::include_modules!();
+/// The generated code is not ours to lint
+#[allow(clippy::all, non_local_definitions, mismatched_lifetime_syntaxes, unknown_lints)]
+mod generated {
+	slint::include_modules!();
+}
+use generated::*;
 
 enum LoadStage<T> {
 	Loading,
 	Loaded(SystemTime, T),
 }
 
-struct DirectoryReader {
-	directory: UIDirectoryInfo,
-	pathIndex: HashMap<String, usize>,
-	receiver: Receiver<FileLoaderAction>,
-}
-
-impl DirectoryReader {
-	fn make(path: &str, status: &str, files: SharedVector<UIFile>, receiver: Receiver<FileLoaderAction>) -> Self {
-		let mut pathIndex = HashMap::new();
-		for (i, f) in files.iter().enumerate() {
-			pathIndex.insert(f.fullPath.to_string(), i);
-		}
-		
-		Self {
-			directory: UIDirectoryInfo {
-				files: ModelRc::new(SharedVectorModel::from(files)),
-				fullPath: SharedString::from(path),
-				status: SharedString::from(status),
-			},
-			pathIndex,
-			receiver,
-		}
-	}
-}
-
 impl Display for UIFile {
 	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
 		fmt.write_str(self.name.as_str())?;
@@ -71,9 +100,152 @@ impl Display for UIFile {
 	}
 }
 
+struct Explorer {
+	app: Rc<HomeApp>,
+	dirReader: Rc<RwLock<Option<DirectoryReader>>>,
+	/// File operations started from this window that are still running
+	ops: Rc<RefCell<Vec<RunningOp>>>,
+	/// What the context menu offers
+	actions: Rc<RefCell<Registry>>,
+	timers: Vec<Timer>,
+}
+
+impl Explorer {
+	fn open(globalIcon: &Arc<RwLock<GlobalIcons>>, loadAnim: Rc<ImageSequence>, persistent: bool) -> Self {
+		let app = Rc::new(HomeApp::new().expect("Failed to load UI"));
+		initLogic(&app.global());
+		registerCursorToEnd(&app);
+		
+		let mut timers = vec![];
+		
+		let timer = Timer::default();
+		if persistent {
+			windowPersistence(app.clone(), &timer);
+		} else {
+			restoreWindowBox(&app);
+		}
+		timers.push(timer);
+		
+		let dirReader = Rc::new(RwLock::new(None));
+		
+		let timer = Timer::default();
+		playLoadingAnimation(&timer, app.clone(), dirReader.clone(), loadAnim);
+		timers.push(timer);
+		
+		let slot = { globalIcon.write().unwrap().newSlot() };
+		registerFileOpen(app.clone(), dirReader.clone(), globalIcon.clone(), slot);
+		registerSelection(app.clone(), dirReader.clone());
+		registerAnimatedThumbnails(&app);
+		registerPhotoTooltips(&app);
+		registerViewSettings(app.clone());
+		registerFilter(app.clone(), dirReader.clone());
+		registerSearch(app.clone(), dirReader.clone());
+		registerQuickJump(app.clone());
+		
+		let timer = Timer::default();
+		registerSidebar(app.clone(), &timer);
+		timers.push(timer);
+		
+		let ops = Rc::new(RefCell::new(vec![]));
+		let timer = Timer::default();
+		pollFileOps(app.clone(), ops.clone(), &timer);
+		timers.push(timer);
+		registerDrag(app.clone(), dirReader.clone(), ops.clone());
+		let timer = Timer::default();
+		registerClipboard(app.clone(), dirReader.clone(), ops.clone(), &timer);
+		timers.push(timer);
+		registerRename(app.clone(), dirReader.clone(), globalIcon.clone());
+		registerJournal(app.clone(), dirReader.clone(), ops.clone());
+		registerNew(app.clone(), dirReader.clone(), globalIcon.clone());
+		let actions = registerActions(app.clone(), dirReader.clone(), ops.clone());
+		registerOpenWith(app.clone(), &actions);
+		let timer = Timer::default();
+		registerProperties(app.clone(), &actions, &timer);
+		timers.push(timer);
+		let timer = Timer::default();
+		registerPreview(app.clone(), dirReader.clone(), &timer);
+		timers.push(timer);
+		let timer = Timer::default();
+		registerViewer(app.clone(), dirReader.clone(), &timer);
+		timers.push(timer);
+		
+		let tApp = app.clone();
+		let tReader = dirReader.clone();
+		let tIcon = globalIcon.clone();
+		let timer = Timer::default();
+		timer.start(TimerMode::Repeated, Duration::from_secs_f32(1.0 / 15.0), move || {
+			poolMediaChanges(&tReader, &tIcon, slot, &tApp)
+		});
+		timers.push(timer);
+		
+		Self { app, dirReader, ops, actions, timers }
+	}
+	
+	/// Runs the action on the files of the target, like the context menu would
+	fn runAction(&self, id: &str, target: &Target) {
+		let dir = PathBuf::from(&target.dir);
+		let ctx = Context { selection: target.select.iter().map(|name| dir.join(name)).collect(), dir, isVirtual: false };
+		if !self.actions.borrow().run(id, &ctx) {
+			println!("Action {} is not available", id);
+		}
+	}
+	
+	fn show(&self, target: &Target) {
+		if target.dir.is_empty() {
+			self.app.set_data(UIDirectoryInfo {
+				files: Default::default(),
+				fullPath: SharedString::from(""),
+				status: SharedString::from("Unable to find home or default path"),
+				virtualName: Default::default(),
+			});
+		} else {
+			self.app.invoke_onFileOpen(SharedString::from(target.dir.as_str()));
+			if !target.select.is_empty() {
+				selectOnly(&self.app, &self.dirReader, &target.select);
+			}
+		}
+		self.app.show().unwrap();
+	}
+	
+	/// Brings the window to the front for a launch that reused it
+	fn raise(&self) {
+		let window = self.app.window();
+		let (position, size) = (window.position(), window.size());
+		if let Err(err) = activate::activate(position.x, position.y, size.width, size.height) {
+			println!("Failed to raise the window: {}", err);
+		}
+	}
+}
+
 fn main() {
 	let start = SystemTime::now();
 	
+	let args = match Args::parse(env::args().skip(1)) {
+		Ok(ParseResult::Run(args)) => { args }
+		Ok(ParseResult::Help) => {
+			println!("{}", Args::usage());
+			return;
+		}
+		Err(err) => {
+			eprintln!("{}\n\n{}", err, Args::usage());
+			process::exit(2);
+		}
+	};
+	if let Some(dir) = &args.configDir {
+		config::setConfigDir(dir.clone());
+	}
+	
+	if ipc::forward(&args).is_ok() {
+		println!("Handed over to the running instance");
+		return;
+	}
+	let (requestSend, requests) = channel();
+	let listening = ipc::listen(&args, requestSend.clone());
+	#[cfg(target_os = "linux")]
+	let _dbus = dbus_service::serve(requestSend).map_err(|err| println!("{}", err)).ok();
+	
+	index::start(&config::settings());
+	
 	let globalIcon = thread::spawn(move || {
 		let gi = Arc::new(RwLock::new(GlobalIcons::read()));
 		icon::startIconGC(gi.clone());
@@ -81,48 +253,72 @@ fn main() {
 	});
 	
 	println!("Starting GUI...");
-	let app = Rc::new(HomeApp::new().expect("Failed to load UI"));
-	initLogic(&app.global());
-	println!("HomeApp: {:?}", start.elapsed().unwrap());
-	
-	let timer = Timer::default();
-	windowPersistence(app.clone(), &timer);
-	
-	let dirReader = Rc::new(RwLock::new(None));
-	
-	let timer = Timer::default();
-	playLoadingAnimation(&timer, app.clone(), dirReader.clone());
-	
-	println!("playLoadingAnimation: {:?}", start.elapsed().unwrap());
-	
 	let globalIcon = globalIcon.join().unwrap();
 	println!("globalIcon join: {:?}", start.elapsed().unwrap());
+	let loadAnim = Rc::new(ImageSequence::read("loading icon sequence.zip", "frame-", "png", 1, 30.0)
+		.map_err(|err| format!("Failed to load loading icon: {err}")).unwrap());
 	
-	registerFileOpen(app.clone(), dirReader.clone(), globalIcon.clone());
+	let windows = Rc::new(RefCell::new(vec![]));
+	openTargets(&windows, &globalIcon, &loadAnim, &args);
 	
-	openStartingPath(&app);
-	
-	println!("since start: {:?}", start.elapsed().unwrap());
-	
-	let tApp = app.clone();
 	let timer = Timer::default();
-	timer.start(TimerMode::Repeated, Duration::from_secs_f32(1.0 / 15.0), move || {
-		poolMediaChanges(&dirReader, &globalIcon, &tApp)
-	});
+	{
+		let windows = windows.clone();
+		timer.start(TimerMode::Repeated, Duration::from_millis(100), move || {
+			while let Ok(args) = requests.try_recv() {
+				openTargets(&windows, &globalIcon, &loadAnim, &args);
+			}
+		});
+	}
 	
 	println!("since start: {:?}", start.elapsed().unwrap());
-	let start = SystemTime::now();
-	println!("Starting events...");
-	app.show().unwrap();
-	
-	println!("{:?}", start.elapsed().unwrap());
 	println!("Running loop...");
 	slint::run_event_loop().unwrap();
 	println!("closing...");
-	app.hide().unwrap();
+	if listening {
+		ipc::cleanup(&args);
+	}
+	for explorer in windows.borrow().iter() {
+		explorer.app.hide().unwrap();
+	}
+}
+
+/// The first target reuses a visible window unless a new one was asked for, the rest always get their own.
+/// A launch without paths only brings a visible window to the front and leaves its folder alone.
+fn openTargets(windows: &RefCell<Vec<Explorer>>, globalIcon: &Arc<RwLock<GlobalIcons>>, loadAnim: &Rc<ImageSequence>, args: &Args) {
+	let mut windows = windows.borrow_mut();
+	let mut targets = args.targets();
+	if targets.is_empty() {
+		if !args.newWindow {
+			if let Some(explorer) = windows.iter().find(|e| e.app.window().is_visible()) {
+				explorer.raise();
+				return;
+			}
+		}
+		targets.push(startingTarget());
+	}
+	
+	for (i, target) in targets.iter().enumerate() {
+		if i == 0 && !args.newWindow {
+			if let Some(explorer) = windows.iter().find(|e| e.app.window().is_visible()) {
+				explorer.show(target);
+				explorer.raise();
+				if args.properties {
+					explorer.runAction("properties", target);
+				}
+				continue;
+			}
+		}
+		let explorer = Explorer::open(globalIcon, loadAnim.clone(), windows.is_empty());
+		explorer.show(target);
+		if args.properties {
+			explorer.runAction("properties", target);
+		}
+		windows.push(explorer);
+	}
 }
 
-fn openStartingPath(app: &HomeApp) {
+fn startingTarget() -> Target {
 	let homePath = home::home_dir().or_else(|| {
 		fs::canonicalize(".").ok().map(|p| {
 			let mut f = p.as_path();
@@ -131,137 +327,1447 @@ fn openStartingPath(app: &HomeApp) {
 		})
 	}).and_then(|p| p.to_str().map(|s| s.to_string()));
 	
-	match homePath {
-		None => {
-			app.set_data(UIDirectoryInfo {
-				files: Default::default(),
-				fullPath: SharedString::from(""),
-				status: SharedString::from("Unable to find home or default path"),
-			})
-		}
-		Some(homePath) => {
-			app.invoke_onFileOpen(SharedString::from(homePath));
-		}
+	Target {
+		dir: homePath.unwrap_or_default(),
+		select: vec![],
 	}
 }
 
-fn registerFileOpen(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, globalIcon: Arc<RwLock<GlobalIcons>>) {
+fn registerFileOpen(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, globalIcon: Arc<RwLock<GlobalIcons>>, slot: IconSlot) {
 	app.clone().on_onFileOpen(move |f| {
 		let f = f.as_str();
-		match fetchInfo(globalIcon.clone(), f) {
-			PathInfo::Fail(d) => { println!("{}: {}", f, d); }
+		match fetchInfo(globalIcon.clone(), slot, f) {
+			PathInfo::Fail(d) => { app.set_pathError(SharedString::from(format!("{}: {}", f, d))); }
 			PathInfo::Dir(d) => {
-				setDir(&app, &dirReader, &globalIcon, d);
+				app.set_pathError(SharedString::default());
+				frecency::visit(&d.fullPath);
+				setDir(&app, &dirReader, &globalIcon, slot, *d);
+			}
+			PathInfo::File if viewer::isImage(Path::new(f)) && !config::settings().externalImageViewer => {
+				app.invoke_onViewImage(SharedString::from(f));
 			}
 			PathInfo::File => {
-				if let Err(err) = open::that(f) { println!("{}", err); }
+				if let Err(err) = open::that(f) {
+					app.set_pathError(SharedString::from(format!("{}: {}", f, err)));
+				}
 			}
 		}
 		app.window().request_redraw();
 	});
 }
 
-fn poolMediaChanges(dirReader: &RwLock<Option<DirectoryReader>>, globalIcon: &RwLock<GlobalIcons>, app: &HomeApp) {
-	let mut discard = false;
+fn registerAnimatedThumbnails(app: &HomeApp) {
+	let thumbnails = RefCell::new(AnimatedThumbnails::default());
+	app.global::<Logic>().on_animationFrame(move |path, time| {
+		thumbnails.borrow_mut().frame(path.as_str(), Duration::from_millis(time.max(0) as u64)).unwrap_or_default()
+	});
+}
+
+fn registerPhotoTooltips(app: &HomeApp) {
+	let known: RefCell<HashMap<String, SharedString>> = Default::default();
+	app.global::<Logic>().on_photoInfo(move |path| {
+		let mut known = known.borrow_mut();
+		if let Some(text) = known.get(path.as_str()) {
+			return text.clone();
+		}
+		// Only formats that carry EXIF are opened
+		let text = match image::ImageFormat::from_path(path.as_str()) {
+			Ok(image::ImageFormat::Jpeg | image::ImageFormat::Tiff | image::ImageFormat::Png | image::ImageFormat::WebP) => {
+				photo::read(Path::new(path.as_str())).map(|p| SharedString::from(p.describe())).unwrap_or_default()
+			}
+			_ => { SharedString::default() }
+		};
+		if known.len() >= 1000 {
+			known.clear();
+		}
+		known.insert(path.to_string(), text.clone());
+		text
+	});
+}
+
+fn registerSelection(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>) {
+	app.on_onToggleSelect(move |path| {
+		let mut reader = dirReader.write().unwrap();
+		if let Some(reader) = reader.as_mut() {
+			if let Some(index) = reader.pathIndex.get(path.as_str()).copied() {
+				reader.update(index, |f| f.selected = !f.selected);
+			}
+		}
+	});
+}
+
+fn registerFilter(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>) {
+	app.clone().on_onFilterChanged(move |text, mode| {
+		let filter = match NameMatcher::new(text.as_str(), MatchMode::fromIndex(mode)) {
+			Ok(f) => { f }
+			Err(err) => {
+				app.set_statusLine(SharedString::from(err));
+				return;
+			}
+		};
+		
+		let mut reader = dirReader.write().unwrap();
+		if let Some(reader) = reader.as_mut() {
+			reader.setFilter(filter);
+			app.set_data(reader.info());
+			app.set_statusLine(SharedString::from(reader.statusLine()));
+		}
+	});
+}
+
+fn registerViewSettings(app: Rc<HomeApp>) {
+	let settings = config::settings();
+	app.set_showHidden(settings.showHidden);
+	app.set_ignoreMode(match settings.ignoreMode {
+		IgnoreMode::Show => { 0 }
+		IgnoreMode::Dim => { 1 }
+		IgnoreMode::Hide => { 2 }
+	});
 	
-	let directory = match dirReader.try_read() {
+	app.clone().on_onViewSettingsChanged(move || {
+		config::updateSettings(|s| {
+			s.showHidden = app.get_showHidden();
+			s.ignoreMode = match app.get_ignoreMode() {
+				1 => { IgnoreMode::Dim }
+				2 => { IgnoreMode::Hide }
+				_ => { IgnoreMode::Show }
+			};
+		});
+		app.invoke_onFileOpen(app.get_data().fullPath);
+	});
+}
+
+fn registerSearch(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>) {
+	let tReader = dirReader.clone();
+	let tApp = app.clone();
+	app.on_onSearch(move |q| {
+		let root = match tReader.read().unwrap().as_ref() {
+			None => { return; }
+			Some(r) => { r.fullPath.clone() }
+		};
+		
+		let fields = SearchFields {
+			root: &root,
+			text: q.text.as_str(),
+			mode: MatchMode::fromIndex(q.mode),
+			minSize: q.minSize.as_str(),
+			maxSize: q.maxSize.as_str(),
+			after: q.after.as_str(),
+			before: q.before.as_str(),
+			kind: Kind::fromIndex(q.kind),
+			content: q.content.as_str(),
+			contentRegex: q.contentRegex,
+		};
+		let query = SearchQuery::parse(&fields, &config::settings());
+		let query = match query {
+			Ok(q) => { q }
+			Err(err) => {
+				tApp.set_statusLine(SharedString::from(err));
+				return;
+			}
+		};
+		
+		let mut description = if q.text.is_empty() { "everything".to_string() } else { format!("\"{}\"", q.text) };
+		if !q.content.is_empty() {
+			description += &format!(" containing \"{}\"", q.content);
+		}
+		let reader = DirectoryReader::makeSearch(&root, search::start(query, description));
+		tApp.set_data(reader.info());
+		tApp.set_filterText(SharedString::default());
+		tApp.set_searching(true);
+		*tReader.write().unwrap() = Some(reader);
+	});
+	
+	app.on_onCancelSearch(move || {
+		if let Some(search) = dirReader.read().unwrap().as_ref().and_then(|r| r.search.as_ref()) {
+			search.cancel();
+		}
+	});
+}
+
+/// Shows the progress of running operations in the status line and reloads the folder once they are done
+fn pollFileOps(app: Rc<HomeApp>, ops: Rc<RefCell<Vec<RunningOp>>>, timer: &Timer) {
+	timer.start(TimerMode::Repeated, Duration::from_millis(200), move || {
+		let mut ops = ops.borrow_mut();
+		if ops.is_empty() {
+			return;
+		}
+		
+		let mut finished = false;
+		ops.retain_mut(|op| {
+			while let Ok(event) = op.receiver.try_recv() {
+				match event {
+					OpEvent::Progress(done) => { op.done = done; }
+					OpEvent::Finished(done, errors) => {
+						app.set_statusLine(SharedString::from(op.summary(&done, &errors)));
+						let entry = match op.kind {
+							_ if done.is_empty() => { None }
+							OpKind::Move => { Some(Entry::Moved(done)) }
+							OpKind::Copy => { Some(Entry::copied(done)) }
+							OpKind::Link => { Some(Entry::Linked(done)) }
+							OpKind::Trash => { Some(Entry::Trashed(done)) }
+							// The archive is a new file the sources stay next to
+							OpKind::Compress => { None }
+						};
+						if let Some(entry) = entry {
+							journal::record(entry);
+						}
+						if let (Some(paths), true) = (&op.pastedCut, errors.is_empty()) {
+							clipboard::clearCut(paths);
+						}
+						finished = true;
+						return false;
+					}
+				}
+			}
+			app.set_statusLine(SharedString::from(op.progress()));
+			true
+		});
+		
+		if finished && app.get_data().virtualName.is_empty() {
+			let status = app.get_statusLine();
+			app.invoke_onFileOpen(app.get_data().fullPath);
+			app.set_statusLine(status);
+		}
+	});
+}
+
+fn registerDrag(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, ops: Rc<RefCell<Vec<RunningOp>>>) {
+	let dragged: Rc<RefCell<Vec<PathBuf>>> = Default::default();
+	
+	let tApp = app.clone();
+	let tDragged = dragged.clone();
+	app.global::<Drag>().on_start(move |path| {
+		let reader = dirReader.read().unwrap();
+		let reader = match reader.as_ref() {
+			None => { return; }
+			Some(r) => { r }
+		};
+		let file = match reader.pathIndex.get(path.as_str()).and_then(|i| reader.files().get(*i)) {
+			None => { return; }
+			Some(f) => { f }
+		};
+		// Dragging a selected file takes the whole selection along
+		let paths = if file.selected { reader.selectedPaths() } else { vec![PathBuf::from(path.as_str())] };
+		
+		let drag = tApp.global::<Drag>();
+		drag.set_icon(file.icon.clone());
+		drag.set_count(paths.len() as i32);
+		drag.set_source(path);
+		drag.set_mode(0);
+		drag.set_active(true);
+		*tDragged.borrow_mut() = paths;
+	});
+	
+	let tApp = app.clone();
+	app.global::<Drag>().on_drop(move |target| {
+		let drag = tApp.global::<Drag>();
+		drag.set_active(false);
+		let sources = dragged.take();
+		
+		let dest = PathBuf::from(target.as_str());
+		if !dest.is_dir() || sources.is_empty() || sources.contains(&dest) {
+			return;
+		}
+		let kind = match drag.get_mode() {
+			1 => { OpKind::Copy }
+			2 => { OpKind::Link }
+			_ => { OpKind::Move }
+		};
+		ops.borrow_mut().push(fileops::start(Operation { kind, sources, dest }));
+	});
+	
+	let tApp = app.clone();
+	app.global::<Drag>().on_cancel(move || {
+		tApp.global::<Drag>().set_active(false);
+	});
+}
+
+/// The clipboard being read for a paste into the folder
+type PendingPaste = Option<(Receiver<Result<Option<Content>, String>>, PathBuf)>;
+
+fn registerClipboard(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, ops: Rc<RefCell<Vec<RunningOp>>>, timer: &Timer) {
+	let tApp = app.clone();
+	app.on_onCopy(move |cut| {
+		let paths = match dirReader.read().unwrap().as_ref() {
+			None => { vec![] }
+			Some(r) => { r.selectedPaths() }
+		};
+		if paths.is_empty() {
+			tApp.set_statusLine(SharedString::from("Nothing selected"));
+			return;
+		}
+		let status = format!("{} {} items", if cut { "Cut" } else { "Copied" }, paths.len());
+		clipboard::setFiles(paths, cut);
+		tApp.set_statusLine(SharedString::from(status));
+	});
+	
+	let pending: Rc<RefCell<PendingPaste>> = Default::default();
+	let tApp = app.clone();
+	let tPending = pending.clone();
+	app.on_onPaste(move || {
+		let data = tApp.get_data();
+		if !data.virtualName.is_empty() {
+			tApp.set_statusLine(SharedString::from("Can not paste into search results"));
+			return;
+		}
+		if tPending.borrow().is_some() {
+			return;
+		}
+		*tPending.borrow_mut() = Some((clipboard::getAsync(), PathBuf::from(data.fullPath.as_str())));
+		tApp.set_statusLine(SharedString::from("Reading the clipboard..."));
+	});
+	
+	timer.start(TimerMode::Repeated, Duration::from_millis(50), move || {
+		let res = match pending.borrow().as_ref().map(|(r, _)| r.try_recv()) {
+			None | Some(Err(TryRecvError::Empty)) => { return; }
+			Some(Ok(res)) => { res }
+			Some(Err(TryRecvError::Disconnected)) => { Err("The clipboard reader stopped".to_string()) }
+		};
+		let dest = match pending.take() {
+			None => { return; }
+			Some((_, dest)) => { dest }
+		};
+		
+		match res {
+			Err(err) => {
+				app.set_statusLine(SharedString::from(format!("Failed to read the clipboard: {}", err)));
+			}
+			Ok(None) | Ok(Some(Content::Text(_))) => {
+				app.set_statusLine(SharedString::from("Nothing to paste"));
+			}
+			Ok(Some(Content::Files { paths, cut })) => {
+				let kind = if cut { OpKind::Move } else { OpKind::Copy };
+				let mut op = fileops::start(Operation { kind, sources: paths.clone(), dest });
+				// Cut files move away, pasting them again would find nothing
+				if cut {
+					op.pastedCut = Some(paths);
+				}
+				ops.borrow_mut().push(op);
+			}
+			Ok(Some(Content::Image { mime, data: bytes })) => {
+				let status = match clipboard::saveImage(&mime, &bytes, &dest) {
+					Ok(path) => { format!("Pasted image as {}", path.display()) }
+					Err(err) => { err }
+				};
+				// The folder may have changed while the clipboard was read
+				if app.get_data().fullPath.as_str() == dest.to_string_lossy() {
+					app.invoke_onFileOpen(app.get_data().fullPath);
+				}
+				app.set_statusLine(SharedString::from(status));
+			}
+		}
+	});
+}
+
+fn registerRename(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, globalIcon: Arc<RwLock<GlobalIcons>>) {
+	app.global::<Rename>().on_validate(|path, name| {
+		let path = Path::new(path.as_str());
+		if path.file_name().map(|n| n.to_string_lossy() == name.as_str()).unwrap_or(false) {
+			return SharedString::default();
+		}
+		match fileops::validateName(path, name.as_str()) {
+			Ok(_) => { SharedString::default() }
+			Err(err) => { SharedString::from(err) }
+		}
+	});
+	
+	let tApp = app.clone();
+	let tReader = dirReader.clone();
+	let tIcon = globalIcon.clone();
+	app.global::<Rename>().on_commit(move |path, name| {
+		let rename = tApp.global::<Rename>();
+		let old = Path::new(path.as_str());
+		if old.file_name().map(|n| n.to_string_lossy() == name.as_str()).unwrap_or(false) {
+			rename.set_path(SharedString::default());
+			return;
+		}
+		
+		match fileops::rename(old, name.as_str()) {
+			Err(err) => {
+				rename.set_error(SharedString::from(err));
+			}
+			Ok(new) => {
+				let renames = vec![(old.to_path_buf(), new)];
+				if let Some(reader) = tReader.write().unwrap().as_mut() {
+					reader.renamed(&renames);
+				}
+				tIcon.write().unwrap().renamed(&renames);
+				journal::record(Entry::Renamed(renames));
+				rename.set_error(SharedString::default());
+				rename.set_path(SharedString::default());
+				tApp.set_statusLine(SharedString::from(format!("Renamed {} to {}", old.display(), name)));
+			}
+		}
+	});
+	
+	let batch: Rc<RefCell<Option<Batch>>> = Default::default();
+	
+	let tApp = app.clone();
+	let tReader = dirReader.clone();
+	let tBatch = batch.clone();
+	app.on_onRenameSelected(move || {
+		let paths = match tReader.read().unwrap().as_ref() {
+			None => { return; }
+			Some(r) => { r.selectedPaths() }
+		};
+		if paths.len() > 1 {
+			tApp.set_bulkRenameCount(paths.len() as i32);
+			*tBatch.borrow_mut() = Some(Batch::new(paths));
+			tApp.set_showBulkRename(true);
+			return;
+		}
+		let path = match paths.into_iter().next() {
+			None => { return; }
+			Some(p) => { p }
+		};
+		let rename = tApp.global::<Rename>();
+		rename.set_error(SharedString::default());
+		rename.set_path(SharedString::from(path.to_string_lossy().as_ref()));
+	});
+	
+	let tBatch = batch.clone();
+	app.on_bulkRenamePreview(move |pattern| {
+		let mut batch = tBatch.borrow_mut();
+		let batch = match batch.as_mut() {
+			None => { return UIRenamePreview::default(); }
+			Some(b) => { b }
+		};
+		match batch.preview(&makePattern(&pattern)) {
+			Err(err) => { UIRenamePreview { error: SharedString::from(err), ..Default::default() } }
+			Ok(rows) => {
+				let ready = rows.iter().all(|r| r.problem.is_empty()) && rows.iter().any(|r| r.old != r.new);
+				let name = |p: &Path| SharedString::from(p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default());
+				let rows: Vec<UIRenameRow> = rows.iter().map(|r| UIRenameRow {
+					old: name(&r.old),
+					new: name(&r.new),
+					problem: SharedString::from(r.problem.as_str()),
+				}).collect();
+				UIRenamePreview { rows: ModelRc::from(Rc::new(VecModel::from(rows)) as Rc<dyn Model<Data=UIRenameRow>>), error: Default::default(), ready }
+			}
+		}
+	});
+	
+	let tApp = app.clone();
+	app.on_bulkRenameApply(move |pattern| {
+		let rows = match batch.borrow_mut().as_mut().map(|b| b.preview(&makePattern(&pattern))) {
+			Some(Ok(rows)) => { rows }
+			Some(Err(err)) => { return SharedString::from(err); }
+			None => { return SharedString::default(); }
+		};
+		if let Some(r) = rows.iter().find(|r| !r.problem.is_empty()) {
+			return SharedString::from(r.problem.as_str());
+		}
+		let renames: Vec<(PathBuf, PathBuf)> = rows.into_iter().filter(|r| r.old != r.new).map(|r| (r.old, r.new)).collect();
+		if let Err(err) = bulkrename::apply(&renames) {
+			return SharedString::from(err);
+		}
+		
+		if let Some(reader) = dirReader.write().unwrap().as_mut() {
+			reader.renamed(&renames);
+		}
+		globalIcon.write().unwrap().renamed(&renames);
+		tApp.set_showBulkRename(false);
+		tApp.set_statusLine(SharedString::from(format!("Renamed {} items, Ctrl+Z undoes it", renames.len())));
+		journal::record(Entry::Renamed(renames));
+		SharedString::default()
+	});
+}
+
+fn registerJournal(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, ops: Rc<RefCell<Vec<RunningOp>>>) {
+	let show = |app: &HomeApp, res: Result<String, String>| {
+		let status = match res {
+			Ok(s) => { s }
+			Err(s) => { s }
+		};
+		if app.get_data().virtualName.is_empty() {
+			app.invoke_onFileOpen(app.get_data().fullPath);
+		}
+		app.set_statusLine(SharedString::from(status));
+	};
+	
+	let tApp = app.clone();
+	app.on_onUndo(move || {
+		show(&tApp, journal::undo());
+	});
+	let tApp = app.clone();
+	app.on_onRedo(move || {
+		show(&tApp, journal::redo());
+	});
+	
+	let tApp = app.clone();
+	app.on_onTrash(move || {
+		let paths = match dirReader.read().unwrap().as_ref() {
+			None => { vec![] }
+			Some(r) => { r.selectedPaths() }
+		};
+		if paths.is_empty() {
+			tApp.set_statusLine(SharedString::from("Nothing selected"));
+			return;
+		}
+		ops.borrow_mut().push(fileops::start(Operation { kind: OpKind::Trash, sources: paths, dest: PathBuf::new() }));
+	});
+}
+
+fn makePattern(pattern: &UIBulkRename) -> Pattern {
+	Pattern {
+		find: pattern.find.to_string(),
+		replace: pattern.replace.to_string(),
+		regex: pattern.regex,
+		template: pattern.template.to_string(),
+		counterStart: pattern.counterStart.trim().parse().unwrap_or(1),
+		case: Case::from(pattern.case),
+	}
+}
+
+/// Creates something in the given folder, returning its path and how to undo it
+type MakeEntry<'a> = dyn Fn(&Path) -> Result<(PathBuf, Entry), String> + 'a;
+
+fn registerNew(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, globalIcon: Arc<RwLock<GlobalIcons>>) {
+	app.on_templates(|| {
+		let templates: Vec<UIPathComponent> = templates::list().into_iter().map(|t| UIPathComponent {
+			fullPath: SharedString::from(t.path.to_string_lossy().as_ref()),
+			name: SharedString::from(t.label),
+		}).collect();
+		ModelRc::from(Rc::new(VecModel::from(templates)) as Rc<dyn Model<Data=UIPathComponent>>)
+	});
+	
+	// Adds the new entry to the listing and starts renaming it
+	let create = move |app: &HomeApp, make: &MakeEntry<'_>| {
+		let mut reader = dirReader.write().unwrap();
+		let reader = match reader.as_mut() {
+			Some(r) if r.search.is_none() => { r }
+			_ => {
+				app.set_statusLine(SharedString::from("Can not create files here"));
+				return;
+			}
+		};
+		let (path, entry) = match make(Path::new(&reader.fullPath)) {
+			Ok(r) => { r }
+			Err(err) => {
+				app.set_statusLine(SharedString::from(err));
+				return;
+			}
+		};
+		journal::record(entry);
+		
+		let icon = {
+			let icons = globalIcon.read().unwrap();
+			if path.is_dir() { icons.folder.asImage() } else { icons.default.asImage() }
+		};
+		reader.push(makeUIFile(&path, icon, false));
+		if let Some(row) = reader.shownRow(reader.files().len() - 1) {
+			app.invoke_reveal(row as i32);
+		}
+		app.set_statusLine(SharedString::from(reader.statusLine()));
+		let rename = app.global::<Rename>();
+		rename.set_error(SharedString::default());
+		rename.set_path(SharedString::from(path.to_string_lossy().as_ref()));
+	};
+	let create = Rc::new(create);
+	
+	let tApp = app.clone();
+	let tCreate = create.clone();
+	app.on_onNewFolder(move || {
+		tCreate(&tApp, &|dir| {
+			let path = templates::newFolder(dir)?;
+			Ok((path.clone(), Entry::Created { path, isDir: true }))
+		});
+	});
+	
+	let tApp = app.clone();
+	app.on_onNewFile(move |template| {
+		create(&tApp, &|dir| {
+			if template.is_empty() {
+				let path = templates::newFile(dir, None)?;
+				return Ok((path.clone(), Entry::Created { path, isDir: false }));
+			}
+			// Undoing deletes the copy of the template unless it was edited
+			let template = PathBuf::from(template.as_str());
+			let path = templates::newFile(dir, Some(&template))?;
+			Ok((path.clone(), Entry::copied(vec![(template, path)])))
+		});
+	});
+}
+
+/// The built in actions of the context menu. More can be registered on the returned registry.
+fn registerActions(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, ops: Rc<RefCell<Vec<RunningOp>>>) -> Rc<RefCell<Registry>> {
+	let registry: Rc<RefCell<Registry>> = Default::default();
+	{
+		let mut r = registry.borrow_mut();
+		let status = |app: &HomeApp, text: String| app.set_statusLine(SharedString::from(text));
+		
+		let tApp = app.clone();
+		r.register(Action {
+			id: "open",
+			label: "Open".to_string(),
+			shortcut: "Enter",
+			group: 0,
+			enabled: Box::new(|c| c.hasSelection()),
+			run: Box::new(move |c| {
+				for path in &c.selection {
+					// Only one folder can be shown at a time
+					if path.is_dir() && c.selection.len() > 1 {
+						continue;
+					}
+					tApp.invoke_onFileOpen(SharedString::from(path.to_string_lossy().as_ref()));
+				}
+			}),
+		});
+		
+		let tApp = app.clone();
+		r.register(Action {
+			id: "cut",
+			label: "Cut".to_string(),
+			shortcut: "Ctrl+X",
+			group: 1,
+			enabled: Box::new(|c| c.hasSelection()),
+			run: Box::new(move |_| tApp.invoke_onCopy(true)),
+		});
+		let tApp = app.clone();
+		r.register(Action {
+			id: "copy",
+			label: "Copy".to_string(),
+			shortcut: "Ctrl+C",
+			group: 1,
+			enabled: Box::new(|c| c.hasSelection()),
+			run: Box::new(move |_| tApp.invoke_onCopy(false)),
+		});
+		let tApp = app.clone();
+		r.register(Action {
+			id: "paste",
+			label: "Paste".to_string(),
+			shortcut: "Ctrl+V",
+			group: 1,
+			enabled: Box::new(|c| !c.isVirtual),
+			run: Box::new(move |_| tApp.invoke_onPaste()),
+		});
+		
+		let tApp = app.clone();
+		r.register(Action {
+			id: "rename",
+			label: "Rename".to_string(),
+			shortcut: "F2",
+			group: 2,
+			enabled: Box::new(|c| c.hasSelection()),
+			run: Box::new(move |_| tApp.invoke_onRenameSelected()),
+		});
+		let tApp = app.clone();
+		r.register(Action {
+			id: "trash",
+			label: "Move to trash".to_string(),
+			shortcut: "Delete",
+			group: 2,
+			enabled: Box::new(|c| c.hasSelection()),
+			run: Box::new(move |_| tApp.invoke_onTrash()),
+		});
+		
+		let tApp = app.clone();
+		r.register(Action {
+			id: "copyPath",
+			label: "Copy path".to_string(),
+			shortcut: "",
+			group: 3,
+			enabled: Box::new(|_| true),
+			run: Box::new(move |c| {
+				let paths: Vec<String> = c.targets().iter().map(|p| p.to_string_lossy().into_owned()).collect();
+				let text = if paths.len() == 1 { "Copied the path".to_string() } else { format!("Copied {} paths", paths.len()) };
+				clipboard::setText(paths.join("\n"));
+				status(&tApp, text);
+			}),
+		});
+		let tApp = app.clone();
+		r.register(Action {
+			id: "terminal",
+			label: "Open terminal here".to_string(),
+			shortcut: "",
+			group: 3,
+			enabled: Box::new(|c| c.folder().is_some()),
+			run: Box::new(move |c| {
+				if let Some(dir) = c.folder() {
+					if let Err(err) = actions::openTerminal(&dir) {
+						status(&tApp, err);
+					}
+				}
+			}),
+		});
+		r.register(Action {
+			id: "compress",
+			label: "Compress".to_string(),
+			shortcut: "",
+			group: 3,
+			enabled: Box::new(|c| c.hasSelection()),
+			run: Box::new(move |c| {
+				let first = &c.selection[0];
+				let dir = first.parent().unwrap_or(Path::new(&c.dir));
+				let name = match (c.selection.len(), first.file_name()) {
+					(1, Some(n)) => { format!("{}.zip", n.to_string_lossy()) }
+					_ => { "Archive.zip".to_string() }
+				};
+				let dest = fileops::uniqueName(&dir.join(name));
+				ops.borrow_mut().push(fileops::start(Operation { kind: OpKind::Compress, sources: c.selection.clone(), dest }));
+			}),
+		});
+	}
+	
+	let current: Rc<RefCell<Option<Context>>> = Default::default();
+	
+	let tReader = dirReader.clone();
+	let tRegistry = registry.clone();
+	app.global::<ContextMenu>().on_invoke(move |id| {
+		let ctx = match tReader.read().unwrap().as_ref() {
+			None => { return; }
+			Some(r) => { Context { dir: PathBuf::from(&r.fullPath), selection: r.selectedPaths(), isVirtual: r.search.is_some() } }
+		};
+		tRegistry.borrow().run(id.as_str(), &ctx);
+	});
+	
+	let tApp = app.clone();
+	let tRegistry = registry.clone();
+	let tCurrent = current.clone();
+	app.global::<ContextMenu>().on_open(move |path| {
+		let ctx = {
+			let mut reader = dirReader.write().unwrap();
+			let reader = match reader.as_mut() {
+				None => { return; }
+				Some(r) => { r }
+			};
+			// Right clicking outside of the selection selects only the clicked file
+			if let Some(index) = reader.pathIndex.get(path.as_str()).copied() {
+				if !reader.files()[index].selected {
+					reader.updateMany(0..reader.files().len(), |i, f| f.selected = i == index);
+				}
+			}
+			Context { dir: PathBuf::from(&reader.fullPath), selection: reader.selectedPaths(), isVirtual: reader.search.is_some() }
+		};
+		
+		let registry = tRegistry.borrow();
+		let mut group = None;
+		let mut separators = 0;
+		let actions: Vec<UIAction> = registry.actions().iter().map(|a| {
+			let separator = group.is_some() && group != Some(a.group);
+			if separator {
+				separators += 1;
+			}
+			group = Some(a.group);
+			UIAction {
+				id: SharedString::from(a.id),
+				label: SharedString::from(a.label.as_str()),
+				shortcut: SharedString::from(a.shortcut),
+				enabled: (a.enabled)(&ctx),
+				separator,
+			}
+		}).collect();
+		
+		let menu = tApp.global::<ContextMenu>();
+		menu.set_actions(ModelRc::from(Rc::new(VecModel::from(actions)) as Rc<dyn Model<Data=UIAction>>));
+		menu.set_separators(separators);
+		*tCurrent.borrow_mut() = Some(ctx);
+		tApp.invoke_showContextMenu();
+	});
+	
+	let tRegistry = registry.clone();
+	app.global::<ContextMenu>().on_run(move |id| {
+		// The menu closes with the click, so the context is not needed after
+		let ctx = match current.borrow_mut().take() {
+			None => { return; }
+			Some(c) => { c }
+		};
+		if !tRegistry.borrow().run(id.as_str(), &ctx) {
+			println!("Action {} is not available", id);
+		}
+	});
+	registry
+}
+
+fn registerOpenWith(app: Rc<HomeApp>, actions: &RefCell<Registry>) {
+	// The files the dialog opens and their mime type
+	let target: Rc<RefCell<(Vec<PathBuf>, String)>> = Default::default();
+	
+	let tApp = app.clone();
+	let tTarget = target.clone();
+	actions.borrow_mut().register(Action {
+		id: "openWith",
+		label: "Open with…".to_string(),
+		shortcut: "",
+		group: 0,
+		enabled: Box::new(|c| c.hasSelection()),
+		run: Box::new(move |c| {
+			let mimeType = mime::forPath(&c.selection[0]);
+			let what = match c.selection.as_slice() {
+				[one] => { one.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default() }
+				many => { format!("{} items", many.len()) }
+			};
+			tApp.set_openWithWhat(SharedString::from(what));
+			tApp.set_openWithMime(SharedString::from(mimeType.as_str()));
+			*tTarget.borrow_mut() = (c.selection.clone(), mimeType);
+			tApp.set_showOpenWith(true);
+		}),
+	});
+	
+	let tTarget = target.clone();
+	app.on_openWithApps(move |all| {
+		let choices = openwith::appsFor(&tTarget.borrow().1);
+		let mut apps = choices.apps;
+		if all {
+			// The registered ones stay on top
+			let others: Vec<_> = openwith::installed().into_iter().filter(|a| !a.noDisplay && !apps.iter().any(|b| b.id == a.id)).collect();
+			apps.extend(others);
+		}
+		let apps: Vec<UIApp> = apps.iter().map(|a| UIApp {
+			id: SharedString::from(a.id.as_str()),
+			name: SharedString::from(a.name.as_str()),
+			comment: SharedString::from(a.comment.as_str()),
+			isDefault: choices.default.as_deref() == Some(a.id.as_str()),
+		}).collect();
+		ModelRc::from(Rc::new(VecModel::from(apps)) as Rc<dyn Model<Data=UIApp>>)
+	});
+	
+	let tApp = app.clone();
+	app.on_openWithLaunch(move |id, makeDefault| {
+		let (files, mimeType) = target.borrow().clone();
+		let chosen = match openwith::find(id.as_str()) {
+			None => { return SharedString::from("The application is not installed anymore"); }
+			Some(a) => { a }
+		};
+		if makeDefault {
+			if let Err(err) = openwith::setDefault(&mimeType, &chosen.id) {
+				return SharedString::from(err);
+			}
+		}
+		if let Err(err) = openwith::launch(&chosen, &files) {
+			return SharedString::from(err);
+		}
+		tApp.set_showOpenWith(false);
+		let what = if files.len() == 1 { "1 item".to_string() } else { format!("{} items", files.len()) };
+		tApp.set_statusLine(SharedString::from(format!("Opened {} with {}", what, chosen.name)));
+		SharedString::default()
+	});
+}
+
+/// The properties of the files being shown, with the count of their sizes while it runs
+struct ShownProperties {
+	paths: Vec<PathBuf>,
+	summary: Summary,
+	sizes: Option<Receiver<SizeEvent>>,
+}
+
+fn registerProperties(app: Rc<HomeApp>, actions: &RefCell<Registry>, timer: &Timer) {
+	let shown: Rc<RefCell<Option<ShownProperties>>> = Default::default();
+	
+	let show = |app: &HomeApp, paths: Vec<PathBuf>| {
+		let summary = properties::summarize(&paths);
+		let perms: Vec<bool> = (0..12).map(|i| {
+			// The order of UIProperties.perms, from the owner read bit down and then the special bits
+			let bit = if i < 9 { 0o400 >> i } else { 0o4000 >> (i - 9) };
+			summary.mode.map(|m| m & bit != 0).unwrap_or(false)
+		}).collect();
+		app.set_properties(UIProperties {
+			title: SharedString::from(summary.title.as_str()),
+			path: SharedString::from(summary.path.as_str()),
+			kind: SharedString::from(summary.kind.as_str()),
+			linkTarget: SharedString::from(summary.linkTarget.as_str()),
+			modified: SharedString::from(summary.modified.as_str()),
+			accessed: SharedString::from(summary.accessed.as_str()),
+			created: SharedString::from(summary.created.as_str()),
+			owner: SharedString::from(summary.owner.as_str()),
+			group: SharedString::from(summary.group.as_str()),
+			perms: ModelRc::from(Rc::new(VecModel::from(perms)) as Rc<dyn Model<Data=bool>>),
+			hasMode: summary.mode.is_some(),
+			hasDirs: summary.hasDirs,
+		});
+		
+		let sizes = if summary.hasDirs {
+			app.set_propertiesSize(SharedString::from("Counting…"));
+			Some(properties::countSizes(paths.clone()))
+		} else {
+			app.set_propertiesSize(SharedString::from(properties::describeSize(summary.direct.bytes)));
+			None
+		};
+		app.set_propertiesContents(SharedString::default());
+		app.set_showProperties(true);
+		ShownProperties { paths, summary, sizes }
+	};
+	
+	let tApp = app.clone();
+	let tShown = shown.clone();
+	actions.borrow_mut().register(Action {
+		id: "properties",
+		label: "Properties".to_string(),
+		shortcut: "Alt+Enter",
+		group: 4,
+		enabled: Box::new(|_| true),
+		run: Box::new(move |c| {
+			*tShown.borrow_mut() = Some(show(&tApp, c.targets()));
+		}),
+	});
+	
+	let tApp = app.clone();
+	let tShown = shown.clone();
+	timer.start(TimerMode::Repeated, Duration::from_millis(100), move || {
+		let mut shown = tShown.borrow_mut();
+		let props = match shown.as_mut() {
+			Some(p) if p.sizes.is_some() => { p }
+			_ => { return; }
+		};
+		let mut latest = None;
+		let mut done = false;
+		while let Some(event) = props.sizes.as_ref().and_then(|s| s.try_recv().ok()) {
+			match event {
+				SizeEvent::Progress(t) => { latest = Some(t); }
+				SizeEvent::Done(t) => {
+					latest = Some(t);
+					done = true;
+				}
+			}
+		}
+		let totals = match latest {
+			None => { return; }
+			Some(t) => { t }
+		};
+		
+		let mut size = properties::describeSize(totals.bytes);
+		if done {
+			size += &format!(", {} on disk", places::humanSize(totals.onDisk));
+			props.sizes = None;
+		} else {
+			size += " …";
+		}
+		let mut contents = properties::describeCounts(totals.files, totals.dirs);
+		if totals.unreadable > 0 {
+			contents += &format!(", {} could not be read", totals.unreadable);
+		}
+		tApp.set_propertiesSize(SharedString::from(size));
+		tApp.set_propertiesContents(SharedString::from(contents));
+	});
+	
+	let tShown = shown.clone();
+	app.on_propertiesClosed(move || {
+		// Dropping the receiver stops the count
+		*tShown.borrow_mut() = None;
+	});
+	
+	let tApp = app.clone();
+	app.on_propertiesApply(move |perms, owner, group, recursive| {
+		let (paths, original) = match shown.borrow().as_ref() {
+			None => { return SharedString::default(); }
+			Some(p) => { (p.paths.clone(), (p.summary.owner.clone(), p.summary.group.clone(), p.summary.mode)) }
+		};
+		let mode = (0..12).fold(0, |mode, i| {
+			let bit = if i < 9 { 0o400 >> i } else { 0o4000 >> (i - 9) };
+			if perms.row_data(i).unwrap_or(false) { mode | bit } else { mode }
+		});
+		// Only what was changed is applied, so items that differ keep what they have
+		let changedText = |new: &str, old: &str| Some(new.trim().to_string()).filter(|n| n != old && !n.is_empty());
+		let change = Change {
+			mode: if Some(mode) != original.2 || recursive { Some(mode) } else { None },
+			owner: changedText(owner.as_str(), &original.0),
+			group: changedText(group.as_str(), &original.1),
+			recursive,
+		};
+		
+		match properties::apply(&paths, &change) {
+			Err(err) => { SharedString::from(err) }
+			Ok(count) => {
+				*shown.borrow_mut() = Some(show(&tApp, paths));
+				let what = if count == 1 { "1 item".to_string() } else { format!("{} items", count) };
+				tApp.set_statusLine(SharedString::from(format!("Changed {}", what)));
+				SharedString::default()
+			}
+		}
+	});
+}
+
+fn registerPreview(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, timer: &Timer) {
+	app.set_showPreview(config::settings().previewPane);
+	// Replacing the receiver discards a preview that is still loading
+	let loading: Rc<RefCell<Option<Receiver<Preview>>>> = Default::default();
+	
+	let tApp = app.clone();
+	let tLoading = loading.clone();
+	app.on_onPreview(move |path| {
+		if tApp.get_showPreview() {
+			tApp.set_previewLoading(true);
+			*tLoading.borrow_mut() = Some(preview::load(PathBuf::from(path.as_str())));
+		}
+	});
+	
+	let tApp = app.clone();
+	let tLoading = loading.clone();
+	app.on_onPreviewToggled(move |shown| {
+		config::updateSettings(|s| s.previewPane = shown);
+		let first = dirReader.read().unwrap().as_ref().and_then(|r| r.selectedPaths().into_iter().next());
+		match first {
+			Some(path) if shown => {
+				tApp.set_previewLoading(true);
+				*tLoading.borrow_mut() = Some(preview::load(path));
+			}
+			_ => {
+				*tLoading.borrow_mut() = None;
+				tApp.set_previewLoading(false);
+				tApp.set_preview(UIPreview::default());
+			}
+		}
+	});
+	
+	let tApp = app.clone();
+	let shownDir = RefCell::new(SharedString::default());
+	timer.start(TimerMode::Repeated, Duration::from_millis(50), move || {
+		// The previewed file belongs to the folder it was picked in
+		let dir = tApp.get_data().fullPath;
+		if *shownDir.borrow() != dir {
+			*shownDir.borrow_mut() = dir;
+			*loading.borrow_mut() = None;
+			tApp.set_previewLoading(false);
+			tApp.set_preview(UIPreview::default());
+		}
+		
+		let done = loading.borrow().as_ref().and_then(|r| r.try_recv().ok());
+		if let Some(p) = done {
+			*loading.borrow_mut() = None;
+			tApp.set_previewLoading(false);
+			tApp.set_preview(previewModel(p));
+		}
+	});
+}
+
+fn previewModel(p: Preview) -> UIPreview {
+	let lineModel = |spans: Vec<UIPreviewSpan>| UIPreviewLine {
+		spans: ModelRc::from(Rc::new(VecModel::from(spans)) as Rc<dyn Model<Data=UIPreviewSpan>>),
+	};
+	let (kind, image, lines, message) = match p.content {
+		preview::Content::Message(m) => { (0, Image::default(), vec![], m) }
+		preview::Content::Image(img) => { (1, img.asImage(), vec![], String::new()) }
+		preview::Content::Text(lines) => {
+			let lines = lines.into_iter().map(|spans| lineModel(spans.into_iter().map(|(style, text)| UIPreviewSpan {
+				text: SharedString::from(text),
+				kind: style.index(),
+			}).collect())).collect();
+			(2, Image::default(), lines, String::new())
+		}
+		preview::Content::Lines(lines) => {
+			let lines = lines.into_iter().map(|l| lineModel(vec![UIPreviewSpan { text: SharedString::from(l), kind: 0 }])).collect();
+			(2, Image::default(), lines, String::new())
+		}
+	};
+	UIPreview {
+		name: SharedString::from(p.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()),
+		kind,
+		image,
+		lines: ModelRc::from(Rc::new(VecModel::from(lines)) as Rc<dyn Model<Data=UIPreviewLine>>),
+		message: SharedString::from(message),
+		metadata: ModelRc::from(Rc::new(VecModel::from(p.metadata.into_iter().map(|(label, value)| UIPreviewField {
+			label: SharedString::from(label),
+			value: SharedString::from(value),
+		}).collect::<Vec<_>>())) as Rc<dyn Model<Data=UIPreviewField>>),
+	}
+}
+
+/// The image open in the viewer and the ones it can step through
+struct ViewerState {
+	/// The images of the folder in the order they are shown
+	files: Vec<PathBuf>,
+	index: usize,
+	loading: Option<Receiver<viewer::Loaded>>,
+	frames: Vec<RgbImg>,
+	/// The frames turned by the rotation, ready to be shown
+	images: Vec<Image>,
+	playback: Playback,
+	rotation: u32,
+	error: String,
+	slideshow: bool,
+	slideShown: Instant,
+}
+
+impl ViewerState {
+	fn open(&mut self, index: usize) {
+		self.index = index;
+		self.loading = Some(viewer::load(self.files[index].clone()));
+		self.slideShown = Instant::now();
+	}
+	
+	fn rotate(&mut self) {
+		self.images = self.frames.iter().map(|f| f.rotated(self.rotation).asImage()).collect();
+	}
+	
+	fn ui(&self) -> UIViewer {
+		let (width, height) = self.frames.first().map(|f| if self.rotation % 2 == 1 { (f.height(), f.width()) } else { (f.width(), f.height()) })
+		                                 .unwrap_or_default();
+		let mut info = format!("{} of {}   {} × {}", self.index + 1, self.files.len(), width, height);
+		if self.frames.len() > 1 {
+			info += &format!("   {} frames", self.frames.len());
+		}
+		UIViewer {
+			image: self.images.get(self.playback.current()).cloned().unwrap_or_default(),
+			width: width as i32,
+			height: height as i32,
+			name: SharedString::from(self.files[self.index].file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()),
+			info: SharedString::from(info),
+			error: SharedString::from(self.error.as_str()),
+			loading: self.loading.is_some(),
+			slideshow: self.slideshow,
+		}
+	}
+}
+
+fn registerViewer(app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, timer: &Timer) {
+	let state: Rc<RefCell<Option<ViewerState>>> = Default::default();
+	
+	let tApp = app.clone();
+	let tState = state.clone();
+	let tReader = dirReader.clone();
+	app.on_onViewImage(move |path| {
+		let path = PathBuf::from(path.as_str());
+		let mut files: Vec<PathBuf> = tReader.read().unwrap().as_ref().map(|r| r.shownPaths()).unwrap_or_default()
+		                                     .into_iter().filter(|p| viewer::isImage(p)).collect();
+		let index = match files.iter().position(|p| *p == path) {
+			Some(i) => { i }
+			None => {
+				files = vec![path];
+				0
+			}
+		};
+		let mut viewer = ViewerState {
+			files,
+			index,
+			loading: None,
+			frames: vec![],
+			images: vec![],
+			playback: Playback::new(vec![]),
+			rotation: 0,
+			error: String::new(),
+			slideshow: false,
+			slideShown: Instant::now(),
+		};
+		viewer.open(index);
+		tApp.set_viewer(viewer.ui());
+		tApp.set_viewerZoom(0.0);
+		tApp.set_showViewer(true);
+		*tState.borrow_mut() = Some(viewer);
+	});
+	
+	let tApp = app.clone();
+	let tState = state.clone();
+	app.on_viewerStep(move |by| {
+		if let Some(viewer) = tState.borrow_mut().as_mut() {
+			let count = viewer.files.len() as i32;
+			let index = if by.abs() == 1 { (viewer.index as i32 + by).rem_euclid(count) } else { (viewer.index as i32 + by).clamp(0, count - 1) };
+			viewer.open(index as usize);
+			tApp.set_viewer(viewer.ui());
+		}
+	});
+	
+	let tApp = app.clone();
+	let tState = state.clone();
+	app.on_viewerRotate(move |turns| {
+		if let Some(viewer) = tState.borrow_mut().as_mut() {
+			viewer.rotation = (viewer.rotation + turns as u32) % 4;
+			viewer.rotate();
+			tApp.set_viewer(viewer.ui());
+			tApp.set_viewerZoom(0.0);
+		}
+	});
+	
+	let tApp = app.clone();
+	let tState = state.clone();
+	app.on_viewerSlideshow(move || {
+		if let Some(viewer) = tState.borrow_mut().as_mut() {
+			viewer.slideshow = !viewer.slideshow;
+			viewer.slideShown = Instant::now();
+			tApp.set_viewer(viewer.ui());
+		}
+	});
+	
+	let tApp = app.clone();
+	let tState = state.clone();
+	app.on_viewerClosed(move || {
+		// The file list follows along to the image looked at last
+		if let Some(viewer) = tState.borrow_mut().take() {
+			if let Some(name) = viewer.files[viewer.index].file_name() {
+				selectOnly(&tApp, &dirReader, &[name.to_string_lossy().into_owned()]);
+			}
+		}
+		tApp.set_viewer(UIViewer::default());
+	});
+	
+	let tApp = app.clone();
+	timer.start(TimerMode::Repeated, Duration::from_millis(15), move || {
+		let mut state = state.borrow_mut();
+		let viewer = match state.as_mut() {
+			None => { return; }
+			Some(v) => { v }
+		};
+		
+		let loaded = viewer.loading.as_ref().and_then(|r| r.try_recv().ok());
+		if let Some(loaded) = loaded {
+			viewer.loading = None;
+			match loaded.frames {
+				Ok(frames) => {
+					viewer.playback = Playback::new(frames.iter().map(|(_, d)| *d).collect());
+					viewer.frames = frames.into_iter().map(|(f, _)| f).collect();
+					viewer.error = String::new();
+				}
+				Err(err) => {
+					viewer.playback = Playback::new(vec![]);
+					viewer.frames = vec![];
+					viewer.error = err;
+				}
+			}
+			viewer.rotation = 0;
+			viewer.rotate();
+			viewer.slideShown = Instant::now();
+			tApp.set_viewer(viewer.ui());
+			tApp.set_viewerZoom(0.0);
+			return;
+		}
+		
+		if viewer.slideshow && viewer.loading.is_none() && viewer.slideShown.elapsed() >= viewer::SLIDESHOW_INTERVAL {
+			let next = (viewer.index + 1) % viewer.files.len();
+			viewer.open(next);
+			tApp.set_viewer(viewer.ui());
+		}
+		if viewer.playback.advance().is_some() {
+			tApp.set_viewer(viewer.ui());
+		}
+	});
+}
+
+fn registerSidebar(app: Rc<HomeApp>, timer: &Timer) {
+	app.set_showSidebar(!config::settings().hideSidebar);
+	app.on_onSidebarToggled(|shown| {
+		config::updateSettings(|s| s.hideSidebar = !shown);
+	});
+	
+	let (send, receiver) = channel();
+	places::watch(send);
+	let tApp = app.clone();
+	timer.start(TimerMode::Repeated, Duration::from_millis(500), move || {
+		while let Ok(p) = receiver.try_recv() {
+			tApp.set_places(placesModel(&p.userDirs));
+			tApp.set_bookmarks(placesModel(&p.bookmarks));
+			tApp.set_mounts(ModelRc::from(Rc::new(VecModel::from(p.mounts.iter().map(|m| UIPlace {
+				label: SharedString::from(m.label.as_str()),
+				fullPath: SharedString::from(dirString(&m.path)),
+				used: 1.0 - m.free as f32 / m.total as f32,
+				detail: SharedString::from(format!("{} free of {}", places::humanSize(m.free), places::humanSize(m.total))),
+			}).collect::<Vec<_>>())) as Rc<dyn Model<Data=UIPlace>>));
+		}
+	});
+	
+	let change = |app: Rc<HomeApp>, res: Result<(), String>| {
+		match res {
+			Ok(_) => {
+				let all: Vec<Place> = bookmarks::read().into_iter().map(|b| Place { label: b.label, path: b.path }).collect();
+				app.set_bookmarks(placesModel(&all));
+			}
+			Err(err) => { app.set_statusLine(SharedString::from(format!("Failed to save bookmarks: {}", err))); }
+		}
+	};
+	let tApp = app.clone();
+	app.on_onBookmarkAdd(move || {
+		let dir = tApp.get_data().fullPath;
+		if !dir.is_empty() {
+			change(tApp.clone(), bookmarks::add(PathBuf::from(dir.as_str().trim_end_matches(std::path::MAIN_SEPARATOR))));
+		}
+	});
+	let tApp = app.clone();
+	app.on_onBookmarkRemove(move |index| {
+		change(tApp.clone(), bookmarks::remove(index as usize));
+	});
+	let tApp = app.clone();
+	app.on_onBookmarkMove(move |from, to| {
+		if to >= 0 {
+			change(tApp.clone(), bookmarks::reorder(from as usize, to as usize));
+		}
+	});
+}
+
+fn placesModel(places: &[Place]) -> ModelRc<UIPlace> {
+	ModelRc::from(Rc::new(VecModel::from(places.iter().map(|p| UIPlace {
+		label: SharedString::from(p.label.as_str()),
+		fullPath: SharedString::from(dirString(&p.path)),
+		used: -1.0,
+		detail: Default::default(),
+	}).collect::<Vec<_>>())) as Rc<dyn Model<Data=UIPlace>>)
+}
+
+/// Directory paths end with a separator everywhere in the UI
+fn dirString(path: &Path) -> String {
+	let str = path.to_string_lossy();
+	if str.ends_with(std::path::MAIN_SEPARATOR) {
+		str.into_owned()
+	} else {
+		format!("{}{}", str, std::path::MAIN_SEPARATOR)
+	}
+}
+
+fn registerQuickJump(app: Rc<HomeApp>) {
+	app.on_quickJumpQuery(|text| {
+		let entries: Vec<UIJumpEntry> = quickjump::query(text.as_str()).into_iter().map(|c| UIJumpEntry {
+			fullPath: SharedString::from(c.path),
+			label: SharedString::from(c.label),
+			source: SharedString::from(c.source),
+		}).collect();
+		ModelRc::from(Rc::new(VecModel::from(entries)) as Rc<dyn Model<Data=UIJumpEntry>>)
+	});
+}
+
+/// Selects the files with the given names, clears any other selection and scrolls the first one into view
+fn selectOnly(app: &HomeApp, dirReader: &RwLock<Option<DirectoryReader>>, names: &[String]) {
+	let mut reader = dirReader.write().unwrap();
+	let reader = match reader.as_mut() {
+		None => { return; }
+		Some(r) => { r }
+	};
+	
+	let mut found = None;
+	for i in 0..reader.files().len() {
+		let file = &reader.files()[i];
+		let selected = names.iter().any(|n| file.name == n.as_str());
+		if selected && found.is_none() { found = Some(i); }
+		if file.selected != selected {
+			reader.update(i, |f| f.selected = selected);
+		}
+	}
+	match found.and_then(|i| reader.shownRow(i)) {
+		None => { println!("{:?} not found in {}", names, reader.fullPath); }
+		Some(row) => { app.invoke_reveal(row as i32); }
+	}
+}
+
+fn poolMediaChanges(dirReader: &RwLock<Option<DirectoryReader>>, globalIcon: &Arc<RwLock<GlobalIcons>>, slot: IconSlot, app: &HomeApp) {
+	let mut directory = match dirReader.try_write() {
 		Ok(l) => { l }
 		Err(_) => { return; }
 	};
-	let dReader = match directory.deref() {
+	let dReader = match directory.as_mut() {
 		None => { return; }
 		Some(s) => { s }
 	};
 	
-	let mut model = None;
-	let mut dirty = false;
+	if dReader.search.is_some() {
+		poolSearchResults(dReader, globalIcon, slot);
+		if dReader.files().is_empty() {
+			app.set_data(dReader.info());
+		}
+		app.set_searching(dReader.isLoading());
+		app.set_statusLine(SharedString::from(dReader.statusLine()));
+	}
+	
+	let receiver = match &dReader.receiver {
+		None => { return; }
+		Some(r) => { r }
+	};
+	
+	let mut makeUI = false;
 	
 	let mut dirtyPos = HashMap::new();
 	
 	let mut defaultIcon: Option<Image> = None;
+	let mut ends = 0;
 	
-	while let Ok(action) = dReader.receiver.try_recv() {
+	while let Ok(action) = receiver.try_recv() {
 		// println!("{}", action);
 		match action {
 			FileLoaderAction::MakeUI => {
-				model = Some(dReader.directory.clone());
-				dirty = true;
+				makeUI = true;
 			}
 			FileLoaderAction::UpdateFile(data) => {
 				let path = data.path;
 				let img = data.image;
 				
 				if let Some(index) = dReader.pathIndex.get(&path) {
-					dirty = true;
 					dirtyPos.insert(*index, img);
 					// if dirtyPos.len() >= 50 { break; }
 				}
 			}
 			FileLoaderAction::End => {
-				discard = true;
+				ends += 1;
 			}
 		}
 	}
-	drop(directory);
 	
-	if dirty {
-		let mut model = match model {
-			None => { app.get_data() }
-			Some(m) => { m }
-		};
-		
-		match dirtyPos.len() {
-			0 => {}
-			1..=5 => {
-				for (pos, image) in dirtyPos {
-					let mut file = model.files.row_data(pos).unwrap();
-					
-					file.icon = image.asImageCached(globalIcon, &mut defaultIcon);
-					
-					model.files.set_row_data(pos, file);
-				}
-			}
-			_ => {
-				let mut fileVec: SharedVector<UIFile> = Default::default();
-				for x in model.files.iter() {
-					fileVec.push(x);
-				}
-				
-				let slice = fileVec.make_mut_slice();
-				for (pos, image) in dirtyPos {
-					slice[pos].icon = image.asImageCached(globalIcon, &mut defaultIcon);
-				}
-				
-				model = UIDirectoryInfo {
-					files: ModelRc::new(SharedVectorModel::from(fileVec)),
-					fullPath: model.fullPath,
-					status: model.status,
-				};
+	match dirtyPos.len() {
+		0 => {}
+		1..=5 => {
+			for (pos, image) in dirtyPos {
+				let icon = image.asImageCached(globalIcon, &mut defaultIcon);
+				dReader.update(pos, |f| {
+					f.icon = icon;
+					f.animated = image.isAnimated();
+				});
 			}
 		}
-		
-		app.set_data(model);
+		_ => {
+			let keys: Vec<usize> = dirtyPos.keys().copied().collect();
+			dReader.updateMany(keys.into_iter(), |pos, f| {
+				f.icon = dirtyPos[&pos].asImageCached(globalIcon, &mut defaultIcon);
+				f.animated = dirtyPos[&pos].isAnimated();
+			});
+		}
 	}
 	
-	if discard {
-		*dirReader.write().unwrap() = None;
+	if makeUI {
+		app.set_data(dReader.info());
+		app.set_statusLine(SharedString::from(dReader.statusLine()));
+	}
+	
+	dReader.iconLoads = dReader.iconLoads.saturating_sub(ends);
+	if dReader.iconLoads == 0 {
+		dReader.receiver = None;
+		dReader.iconSend = None;
 		// println!("Done updating");
 	}
 }
 
-fn playLoadingAnimation(timer: &Timer, app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>) {
-	let start = SystemTime::now();
-	let load = ImageSequence::read("loading icon sequence.zip", "frame-", "png", 1, 30.0)
-		.map_err(|err| format!("Failed to load loading icon: {err}")).unwrap();
+/// Moves new results into the listing and starts loading their icons
+fn poolSearchResults(dReader: &mut DirectoryReader, globalIcon: &Arc<RwLock<GlobalIcons>>, slot: IconSlot) {
+	let mut found = vec![];
+	if let Some(search) = dReader.search.as_mut() {
+		while let Ok(event) = search.receiver.try_recv() {
+			match event {
+				SearchEvent::Found(path, snippets) => { found.push((path, snippets)); }
+				SearchEvent::Done => { search.done = true; }
+			}
+			// Leave the rest for the next tick so the UI stays responsive
+			if found.len() >= 2000 { break; }
+		}
+		search.found += found.len();
+	}
+	if found.is_empty() {
+		return;
+	}
 	
-	// println!("loading icon sequence {:?}", start.elapsed().unwrap());
+	let icon = { globalIcon.read().unwrap().default.clone() }.asImage();
+	for (path, snippets) in &found {
+		let mut file = makeUIFile(path, icon.clone(), false);
+		file.detail = SharedString::from(snippets.join("\n"));
+		dReader.push(file);
+	}
+	
+	// Only the new results, the loads for earlier ones are still running or done
+	let paths: Vec<PathBuf> = found.into_iter().map(|(path, _)| path).collect();
+	match &dReader.iconSend {
+		Some(send) if dReader.receiver.is_some() => { icon::loadMoreIcons(globalIcon.clone(), slot, Arc::new(paths), send.clone()); }
+		_ => {
+			let (send, receiver) = channel();
+			icon::loadAsyncIcons(globalIcon.clone(), slot, Arc::new(paths), send.clone());
+			dReader.receiver = Some(receiver);
+			dReader.iconSend = Some(send);
+		}
+	}
+	dReader.iconLoads += 1;
+}
+
+fn playLoadingAnimation(timer: &Timer, app: Rc<HomeApp>, dirReader: Rc<RwLock<Option<DirectoryReader>>>, load: Rc<ImageSequence>) {
+	let start = SystemTime::now();
 	
 	let mut loadStart = SystemTime::now();
 	
 	let mut lastLoading = true;
 	timer.start(TimerMode::Repeated, load.timePerFrame(), move || {
 		let loading = match dirReader.try_read() {
-			Ok(l) => { l.deref().is_some() }
+			Ok(l) => { l.as_ref().map(|r| r.isLoading()).unwrap_or(false) }
 			Err(_) => { true }
 		};
 		if !lastLoading && loading {
@@ -280,9 +1786,12 @@ fn playLoadingAnimation(timer: &Timer, app: Rc<HomeApp>, dirReader: Rc<RwLock<Op
 	});
 }
 
-fn setDir(app: &HomeApp, dirReader: &RwLock<Option<DirectoryReader>>, globalIcon: &RwLock<GlobalIcons>, d: DirectoryReader) {
+fn setDir(app: &HomeApp, dirReader: &RwLock<Option<DirectoryReader>>, globalIcon: &Arc<RwLock<GlobalIcons>>, slot: IconSlot, d: DirectoryReader) {
 	*dirReader.write().unwrap() = Some(d);
-	poolMediaChanges(dirReader, globalIcon, app)
+	app.set_filterText(SharedString::default());
+	app.set_searching(false);
+	app.global::<Rename>().set_path(SharedString::default());
+	poolMediaChanges(dirReader, globalIcon, slot, app)
 }
 
 fn initLogic(logic: &Logic) {
@@ -307,17 +1816,81 @@ fn initLogic(logic: &Logic) {
 	});
 	
 	logic.on_separator(|| SharedString::from(std::path::MAIN_SEPARATOR));
+	
+	logic.on_completePath(|text| {
+		let comps: Vec<UIPathComponent> = pathinput::complete(text.as_str()).iter().map(|p| UIPathComponent {
+			fullPath: SharedString::from(format!("{}{}", p.to_string_lossy(), std::path::MAIN_SEPARATOR)),
+			name: SharedString::from(p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()),
+		}).collect();
+		ModelRc::from(Rc::new(VecModel::from(comps)) as Rc<dyn Model<Data=UIPathComponent>>)
+	});
+	logic.on_subdirectories(|dir| {
+		let comps: Vec<UIPathComponent> = pathinput::subdirectories(dir.as_str(), config::settings().showHidden).iter().map(|p| UIPathComponent {
+			fullPath: SharedString::from(format!("{}{}", p.to_string_lossy(), std::path::MAIN_SEPARATOR)),
+			name: SharedString::from(p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()),
+		}).collect();
+		ModelRc::from(Rc::new(VecModel::from(comps)) as Rc<dyn Model<Data=UIPathComponent>>)
+	});
+	logic.on_completeCommon(|text| SharedString::from(pathinput::completeCommon(text.as_str())));
+	logic.on_validatePath(|text| SharedString::from(pathinput::validate(text.as_str())));
+}
+
+/// Moves the cursor of the focused text input to the end, once the current event is handled
+fn registerCursorToEnd(app: &Rc<HomeApp>) {
+	let weak = Rc::downgrade(app);
+	app.global::<Logic>().on_cursorToEnd(move || {
+		let weak = weak.clone();
+		Timer::single_shot(Duration::ZERO, move || {
+			if let Some(app) = weak.upgrade() {
+				let end = SharedString::from(slint::platform::Key::End);
+				app.window().dispatch_event(WindowEvent::KeyPressed { text: end.clone() });
+				app.window().dispatch_event(WindowEvent::KeyReleased { text: end });
+			}
+		});
+	});
+	
+	let weak = Rc::downgrade(app);
+	app.global::<Logic>().on_selectStem(move |name| {
+		// Hidden files like .bashrc have no extension, neither do folders but their names rarely have dots
+		let stem = match name.rfind('.') {
+			Some(i) if i > 0 => { &name[..i] }
+			_ => { name.as_str() }
+		};
+		let count = stem.chars().count();
+		let weak = weak.clone();
+		Timer::single_shot(Duration::ZERO, move || {
+			if let Some(app) = weak.upgrade() {
+				let press = |key: slint::platform::Key| {
+					let text = SharedString::from(key);
+					app.window().dispatch_event(WindowEvent::KeyPressed { text: text.clone() });
+					app.window().dispatch_event(WindowEvent::KeyReleased { text });
+				};
+				press(slint::platform::Key::Home);
+				let shift = SharedString::from(slint::platform::Key::Shift);
+				app.window().dispatch_event(WindowEvent::KeyPressed { text: shift.clone() });
+				for _ in 0..count {
+					press(slint::platform::Key::RightArrow);
+				}
+				app.window().dispatch_event(WindowEvent::KeyReleased { text: shift });
+			}
+		});
+	});
+}
+
+fn restoreWindowBox(app: &HomeApp) -> (Option<WindowBox>, WindowBox) {
+	let org = config::readWindowBox();
+	let s = org.unwrap_or_else(|| WindowBox::new(100, 100, 800, 600));
+	
+	let win = app.window();
+	win.set_size(WindowSize::Physical(PhysicalSize::new(s.width, s.height)));
+	win.set_position(WindowPosition::Physical(PhysicalPosition::new(s.x, s.y)));
+	(org, s)
 }
 
 fn windowPersistence(app: Rc<HomeApp>, timer: &Timer) {
 	let windowState;
 	{
-		let org = config::readWindowBox();
-		let s = org.unwrap_or_else(|| WindowBox::new(100, 100, 800, 600));
-		
-		let win = app.window();
-		win.set_size(WindowSize::Physical(PhysicalSize::new(s.width, s.height)));
-		win.set_position(WindowPosition::Physical(PhysicalPosition::new(s.x, s.y)));
+		let (org, s) = restoreWindowBox(&app);
 		
 		windowState = Arc::new(Mutex::new(WindowInfo::new(s)));
 		config::watchState(windowState.clone(), org);
@@ -331,12 +1904,32 @@ fn windowPersistence(app: Rc<HomeApp>, timer: &Timer) {
 
 enum PathInfo {
 	Fail(String),
-	Dir(DirectoryReader),
+	Dir(Box<DirectoryReader>),
 	File,
 }
 
+/// Resolves . and .. without touching the disk, relative paths are taken from the working directory
+#[cfg(windows)]
+fn normalizeVirtually(path: &Path) -> Option<PathBuf> {
+	path.normalize_virtually().ok().map(|p| p.into_path_buf())
+}
+
+/// normpath only has normalize_virtually on Windows, elsewhere normalize resolves symlinks and fails for missing paths
+#[cfg(not(windows))]
+fn normalizeVirtually(path: &Path) -> Option<PathBuf> {
+	let mut res = if path.is_absolute() { PathBuf::new() } else { env::current_dir().ok()? };
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => { res.pop(); }
+			c => { res.push(c); }
+		}
+	}
+	Some(res)
+}
+
 fn normalizePath(path: &str) -> String {
-	if let Ok(norm) = Path::new(path).normalize_virtually() {
+	if let Some(norm) = normalizeVirtually(Path::new(path)) {
 		let str = norm.as_os_str().to_str().unwrap().to_string();
 		if fs::metadata(&norm).map(|m| m.is_dir()).unwrap_or(false) {
 			return if str.ends_with(std::path::MAIN_SEPARATOR) {
@@ -355,8 +1948,21 @@ fn normalizePath(path: &str) -> String {
 	path.to_string()
 }
 
-fn fetchInfo(state: Arc<RwLock<GlobalIcons>>, path: &str) -> PathInfo {
-	let path = &normalizePath(path);
+fn makeUIFile(path: &Path, icon: Image, dimmed: bool) -> UIFile {
+	UIFile {
+		name: SharedString::from(path.file_name().and_then(|o| o.to_str())
+		                             .map(|s| s.to_string()).unwrap_or("".to_string())),
+		fullPath: SharedString::from(path.to_str().unwrap_or("")),
+		icon,
+		selected: false,
+		dimmed,
+		detail: Default::default(),
+		animated: false,
+	}
+}
+
+fn fetchInfo(state: Arc<RwLock<GlobalIcons>>, slot: IconSlot, path: &str) -> PathInfo {
+	let path = &normalizePath(&pathinput::expand(path));
 	
 	for x in ["", ".", "./"] {
 		if x.eq(path) {
@@ -367,25 +1973,29 @@ fn fetchInfo(state: Arc<RwLock<GlobalIcons>>, path: &str) -> PathInfo {
 	match fs::read_dir(path) {
 		Ok(rd) => {
 			//Collect
-			let paths: Arc<Vec<PathBuf>> =Arc::new(rd.into_iter().filter_map(|p| p.ok()).map(|p| p.path()).collect());
+			let filter = EntryFilter::forDir(Path::new(path), &config::settings());
+			let mut dimmed = vec![];
+			let paths: Arc<Vec<PathBuf>> = Arc::new(rd.into_iter().filter_map(|p| p.ok()).map(|p| p.path()).filter(|p| {
+				match filter.classify(p) {
+					Visibility::Shown => { dimmed.push(false); }
+					Visibility::Dimmed => { dimmed.push(true); }
+					Visibility::Hidden => { return false; }
+				}
+				true
+			}).collect());
 			
 			let (send, receiver) = channel();
 			send.send(FileLoaderAction::MakeUI).unwrap();
 			
-			icon::loadAsyncIcons(state.clone(), paths.clone(), send);
+			icon::loadAsyncIcons(state.clone(), slot, paths.clone(), send);
 			
 			let icon = { state.read().unwrap().default.clone() }.asImage();
 			
-			let files: SharedVector<UIFile> = paths.deref().iter().map(|path| UIFile {
-				name: SharedString::from(path.file_name().and_then(|o| o.to_str())
-				                             .map(|s| s.to_string()).unwrap_or("".to_string())),
-				fullPath: SharedString::from(path.to_str().unwrap_or("")),
-				icon: icon.clone(),
-			}).collect();
+			let files: Vec<UIFile> = paths.deref().iter().zip(dimmed).map(|(path, dimmed)| makeUIFile(path, icon.clone(), dimmed)).collect();
 			
 			let status = if files.is_empty() { "This folder is empty" } else { "Ok directory" };
 			
-			PathInfo::Dir(DirectoryReader::make(path, status, files, receiver))
+			PathInfo::Dir(Box::new(DirectoryReader::make(path, status, files, receiver)))
 		}
 		Err(err) => {
 			if let Ok(meta) = fs::metadata(path) {
@@ -0,0 +1,111 @@
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum MatchMode {
+	Substring,
+	Glob,
+	Regex,
+	Fuzzy,
+}
+
+impl MatchMode {
+	/// Same order as the mode selectors in the UI
+	pub fn fromIndex(index: i32) -> Self {
+		match index {
+			1 => { MatchMode::Glob }
+			2 => { MatchMode::Regex }
+			3 => { MatchMode::Fuzzy }
+			_ => { MatchMode::Substring }
+		}
+	}
+}
+
+/// Matches file names. Case is ignored unless the pattern contains an upper case letter.
+pub enum NameMatcher {
+	All,
+	Substring(String, bool),
+	Glob(GlobMatcher),
+	Regex(Regex),
+	Fuzzy(String),
+}
+
+impl NameMatcher {
+	pub fn new(pattern: &str, mode: MatchMode) -> Result<Self, String> {
+		if pattern.is_empty() {
+			return Ok(NameMatcher::All);
+		}
+		let ignoreCase = !pattern.chars().any(|c| c.is_uppercase());
+		
+		match mode {
+			MatchMode::Substring => {
+				let p = if ignoreCase { pattern.to_lowercase() } else { pattern.to_string() };
+				Ok(NameMatcher::Substring(p, ignoreCase))
+			}
+			MatchMode::Glob => {
+				GlobBuilder::new(pattern)
+					.case_insensitive(ignoreCase)
+					.literal_separator(true)
+					.build()
+					.map(|g| NameMatcher::Glob(g.compile_matcher()))
+					.map_err(|err| format!("Invalid glob: {}", err))
+			}
+			MatchMode::Regex => {
+				RegexBuilder::new(pattern)
+					.case_insensitive(ignoreCase)
+					.build()
+					.map(NameMatcher::Regex)
+					.map_err(|err| format!("Invalid regex: {}", err))
+			}
+			MatchMode::Fuzzy => {
+				Ok(NameMatcher::Fuzzy(pattern.to_lowercase()))
+			}
+		}
+	}
+	
+	pub fn isAll(&self) -> bool { matches!(self, NameMatcher::All) }
+	
+	pub fn isMatch(&self, name: &str) -> bool {
+		match self {
+			NameMatcher::All => { true }
+			NameMatcher::Substring(p, ignoreCase) => {
+				if *ignoreCase { name.to_lowercase().contains(p.as_str()) } else { name.contains(p.as_str()) }
+			}
+			NameMatcher::Glob(g) => { g.is_match(name) }
+			NameMatcher::Regex(r) => { r.is_match(name) }
+			NameMatcher::Fuzzy(p) => { fuzzyScore(p, name).is_some() }
+		}
+	}
+}
+
+/// Scores how well the characters of pattern appear in order inside text, None if they don't.
+/// Consecutive characters and characters at the start of a word are worth more. The pattern has to be lower case.
+pub fn fuzzyScore(pattern: &str, text: &str) -> Option<i64> {
+	let mut score = 0;
+	let mut pat = pattern.chars().peekable();
+	let mut prev: Option<char> = None;
+	let mut streak = 0;
+	
+	for c in text.chars() {
+		let p = match pat.peek() {
+			None => { break; }
+			Some(p) => { *p }
+		};
+		
+		if c.to_lowercase().eq(p.to_lowercase()) {
+			let wordStart = prev.map(|pc| !pc.is_alphanumeric() || (pc.is_lowercase() && c.is_uppercase())).unwrap_or(true);
+			streak += 1;
+			score += 1 + streak * 2 + if wordStart { 8 } else { 0 };
+			pat.next();
+		} else {
+			streak = 0;
+			score -= 1;
+		}
+		prev = Some(c);
+	}
+	
+	if pat.peek().is_some() {
+		return None;
+	}
+	Some(score)
+}
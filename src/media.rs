@@ -0,0 +1,217 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+/// How long an audio or video file plays, read from its headers. Only the common containers are known.
+pub fn duration(path: &Path) -> Option<Duration> {
+	let ext = path.extension()?.to_string_lossy().to_lowercase();
+	let mut file = File::open(path).ok()?;
+	let secs = match ext.as_str() {
+		"wav" => { wav(&mut file) }
+		"flac" => { flac(&mut file) }
+		"mp4" | "m4a" | "m4v" | "m4b" | "mov" | "3gp" => { mp4(&mut file) }
+		"mp3" => { mp3(&mut file) }
+		"ogg" | "oga" | "ogv" | "opus" => { ogg(&mut file) }
+		_ => { None }
+	}?;
+	Some(Duration::from_secs_f64(secs)).filter(|d| !d.is_zero())
+}
+
+/// Like 1:02:03 or 4:05
+pub fn describe(duration: Duration) -> String {
+	let secs = duration.as_secs();
+	if secs >= 3600 {
+		format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+	} else {
+		format!("{}:{:02}", secs / 60, secs % 60)
+	}
+}
+
+fn readAt(file: &mut File, offset: u64, buf: &mut [u8]) -> Option<()> {
+	file.seek(SeekFrom::Start(offset)).ok()?;
+	file.read_exact(buf).ok()
+}
+
+fn u32le(b: &[u8]) -> u32 { u32::from_le_bytes([b[0], b[1], b[2], b[3]]) }
+
+fn u32be(b: &[u8]) -> u32 { u32::from_be_bytes([b[0], b[1], b[2], b[3]]) }
+
+fn u64be(b: &[u8]) -> u64 { ((u32be(b) as u64) << 32) | u32be(&b[4..]) as u64 }
+
+/// RIFF chunks, the length of the data chunk over the byte rate of the fmt chunk
+fn wav(file: &mut File) -> Option<f64> {
+	let mut head = [0u8; 12];
+	readAt(file, 0, &mut head)?;
+	if &head[0..4] != b"RIFF" || &head[8..12] != b"WAVE" {
+		return None;
+	}
+	let mut offset = 12;
+	let mut byteRate = None;
+	let mut chunk = [0u8; 8];
+	while readAt(file, offset, &mut chunk).is_some() {
+		let size = u32le(&chunk[4..]) as u64;
+		match &chunk[0..4] {
+			b"fmt " => {
+				let mut fmt = [0u8; 12];
+				readAt(file, offset + 8, &mut fmt)?;
+				byteRate = Some(u32le(&fmt[8..])).filter(|r| *r > 0);
+			}
+			b"data" => { return Some(size as f64 / byteRate? as f64); }
+			_ => {}
+		}
+		// Chunks are padded to an even length
+		offset += 8 + size + size % 2;
+	}
+	None
+}
+
+/// Total samples over the sample rate, both in the STREAMINFO block that always comes first
+fn flac(file: &mut File) -> Option<f64> {
+	let mut head = [0u8; 26];
+	readAt(file, 0, &mut head)?;
+	if &head[0..4] != b"fLaC" {
+		return None;
+	}
+	let info = u64be(&head[18..26]);
+	let rate = (info >> 44) & 0xFFFFF;
+	let samples = info & 0xF_FFFF_FFFF;
+	if rate == 0 {
+		return None;
+	}
+	Some(samples as f64 / rate as f64)
+}
+
+/// The duration and time scale in the movie header, moov/mvhd
+fn mp4(file: &mut File) -> Option<f64> {
+	let len = file.metadata().ok()?.len();
+	let moov = findAtom(file, 0, len, b"moov")?;
+	let (start, _) = findAtom(file, moov.0, moov.1, b"mvhd")?;
+	let mut header = [0u8; 32];
+	readAt(file, start, &mut header)?;
+	let (scale, duration) = if header[0] == 1 {
+		(u32be(&header[20..]) as u64, u64be(&header[24..]))
+	} else {
+		(u32be(&header[12..]) as u64, u32be(&header[16..]) as u64)
+	};
+	if scale == 0 {
+		return None;
+	}
+	Some(duration as f64 / scale as f64)
+}
+
+/// The start and end of the content of the first atom of the kind between start and end
+fn findAtom(file: &mut File, mut offset: u64, end: u64, kind: &[u8; 4]) -> Option<(u64, u64)> {
+	let mut head = [0u8; 16];
+	while offset + 8 <= end {
+		readAt(file, offset, &mut head[..8])?;
+		let (headLen, size) = match u32be(&head) as u64 {
+			0 => { (8, end - offset) }
+			1 => {
+				readAt(file, offset, &mut head)?;
+				(16, u64be(&head[8..]))
+			}
+			s => { (8, s) }
+		};
+		if size < headLen {
+			return None;
+		}
+		if &head[4..8] == kind {
+			return Some((offset + headLen, offset + size));
+		}
+		offset += size;
+	}
+	None
+}
+
+/// The frame count of a Xing or VBRI header, or the length at the bitrate of the first frame for constant bitrates
+fn mp3(file: &mut File) -> Option<f64> {
+	let len = file.metadata().ok()?.len();
+	let mut offset = 0;
+	let mut id3 = [0u8; 10];
+	readAt(file, 0, &mut id3)?;
+	if &id3[0..3] == b"ID3" {
+		// The size is stored in 7 bits per byte
+		let size = id3[6..10].iter().fold(0u64, |s, b| (s << 7) | (*b & 0x7F) as u64);
+		offset = 10 + size;
+	}
+	
+	let mut buf = vec![0u8; 64 * 1024];
+	file.seek(SeekFrom::Start(offset)).ok()?;
+	let read = file.read(&mut buf).ok()?;
+	let buf = &buf[..read];
+	let sync = (0..buf.len().saturating_sub(4)).find(|i| buf[*i] == 0xFF && buf[*i + 1] & 0xE0 == 0xE0 && buf[*i + 2] & 0xF0 != 0xF0)?;
+	let frame = &buf[sync..];
+	
+	let version = (frame[1] >> 3) & 3; // 3 is MPEG 1, 2 MPEG 2, 0 MPEG 2.5
+	let layer = (frame[1] >> 1) & 3; // 1 is layer III
+	let mpeg1 = version == 3;
+	const RATES: [u32; 3] = [44100, 48000, 32000];
+	let rate = *RATES.get(((frame[2] >> 2) & 3) as usize)? >> if mpeg1 { 0 } else if version == 2 { 1 } else { 2 };
+	let samplesPerFrame = match (layer, mpeg1) {
+		(3, _) => { 384 }
+		(1, false) => { 576 }
+		_ => { 1152 }
+	};
+	
+	let mono = frame[3] >> 6 == 3;
+	let sideInfo = match (mpeg1, mono) {
+		(true, false) => { 32 }
+		(true, true) | (false, false) => { 17 }
+		(false, true) => { 9 }
+	};
+	let xing = &frame[(4 + sideInfo).min(frame.len())..];
+	if (xing.starts_with(b"Xing") || xing.starts_with(b"Info")) && xing.len() >= 12 && xing[7] & 1 != 0 {
+		let frames = u32be(&xing[8..]);
+		return Some(frames as f64 * samplesPerFrame as f64 / rate as f64);
+	}
+	let vbri = &frame[(4 + 32).min(frame.len())..];
+	if vbri.starts_with(b"VBRI") && vbri.len() >= 18 {
+		let frames = u32be(&vbri[14..]);
+		return Some(frames as f64 * samplesPerFrame as f64 / rate as f64);
+	}
+	
+	// Other layers are too rare to bother with their bitrate tables
+	if layer != 1 {
+		return None;
+	}
+	const BITRATES_V1_L3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+	const BITRATES_V2_L3: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+	let table = if mpeg1 { &BITRATES_V1_L3 } else { &BITRATES_V2_L3 };
+	let kbps = *table.get((frame[2] >> 4) as usize).filter(|b| **b > 0)?;
+	Some((len - offset - sync as u64) as f64 * 8.0 / (kbps as f64 * 1000.0))
+}
+
+/// The granule position of the last page over the sample rate from the identification header
+fn ogg(file: &mut File) -> Option<f64> {
+	let mut first = [0u8; 128];
+	let read = file.read(&mut first).ok()?;
+	let first = &first[..read];
+	if !first.starts_with(b"OggS") {
+		return None;
+	}
+	// The first packet follows the segment table of the first page
+	let packet = &first[(27 + *first.get(26)? as usize).min(first.len())..];
+	let (rate, preSkip) = if packet.starts_with(b"\x01vorbis") && packet.len() >= 16 {
+		(u32le(&packet[12..]) as u64, 0)
+	} else if packet.starts_with(b"OpusHead") && packet.len() >= 12 {
+		// Opus always counts at 48kHz
+		(48000, u16::from_le_bytes([packet[10], packet[11]]) as u64)
+	} else if packet.starts_with(b"\x7fFLAC") && packet.len() >= 31 {
+		// The mapping header, then a native FLAC header with its STREAMINFO
+		((u64be(&packet[23..31]) >> 44) & 0xFFFFF, 0)
+	} else {
+		return None;
+	};
+	if rate == 0 {
+		return None;
+	}
+	
+	let len = file.metadata().ok()?.len();
+	let start = len.saturating_sub(64 * 1024);
+	let mut tail = vec![0u8; (len - start) as usize];
+	readAt(file, start, &mut tail)?;
+	let last = (0..tail.len().saturating_sub(14)).rev().find(|i| tail[*i..].starts_with(b"OggS"))?;
+	let granule = u64::from_le_bytes(tail[last + 6..last + 14].try_into().ok()?);
+	Some(granule.saturating_sub(preSkip) as f64 / rate as f64)
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobMatcher};
+use lazy_static::lazy_static;
+
+use crate::config::dataDirs;
+
+lazy_static! {
+	static ref DATABASE: Database = Database::read();
+}
+
+pub const DIRECTORY: &str = "inode/directory";
+pub const UNKNOWN: &str = "application/octet-stream";
+
+struct Glob {
+	weight: u32,
+	mime: String,
+	pattern: String,
+	caseSensitive: bool,
+}
+
+/// What shared-mime-info knows about file names, see https://specifications.freedesktop.org/shared-mime-info-spec/shared-mime-info-spec-latest.html
+#[derive(Default)]
+struct Database {
+	/// "*.ext" patterns by their lowercase extension, which can contain dots like "tar.gz"
+	suffixes: HashMap<String, Vec<Glob>>,
+	/// Whole file names like "makefile", lowercase
+	names: HashMap<String, Vec<Glob>>,
+	/// Everything else
+	others: Vec<(Glob, GlobMatcher)>,
+	aliases: HashMap<String, String>,
+	parents: HashMap<String, Vec<String>>,
+}
+
+impl Database {
+	fn read() -> Self {
+		let mut db = Database::default();
+		// Less important directories first, so the more important ones override aliases
+		for dir in dataDirs().iter().rev().map(|d| d.join("mime")) {
+			if let Ok(text) = fs::read_to_string(dir.join("globs2")) {
+				db.readGlobs(&text);
+			}
+			if let Ok(text) = fs::read_to_string(dir.join("aliases")) {
+				for (alias, mime) in text.lines().filter_map(|l| l.split_once(' ')) {
+					db.aliases.insert(alias.to_string(), mime.to_string());
+				}
+			}
+			if let Ok(text) = fs::read_to_string(dir.join("subclasses")) {
+				for (mime, parent) in text.lines().filter_map(|l| l.split_once(' ')) {
+					let parents = db.parents.entry(mime.to_string()).or_default();
+					if !parents.iter().any(|p| p == parent) {
+						parents.push(parent.to_string());
+					}
+				}
+			}
+		}
+		db
+	}
+	
+	/// Lines look like weight:mime:pattern, optionally followed by :flags
+	fn readGlobs(&mut self, text: &str) {
+		for line in text.lines().filter(|l| !l.starts_with('#')) {
+			let mut parts = line.splitn(4, ':');
+			let (weight, mime, pattern) = match (parts.next().and_then(|w| w.parse().ok()), parts.next(), parts.next()) {
+				(Some(w), Some(m), Some(p)) if !p.is_empty() => { (w, m, p) }
+				_ => { continue; }
+			};
+			let caseSensitive = parts.next().map(|f| f.split(',').any(|f| f == "cs")).unwrap_or(false);
+			let glob = Glob { weight, mime: mime.to_string(), pattern: pattern.to_string(), caseSensitive };
+			
+			let isPlain = |s: &str| !s.contains(['*', '?', '[']);
+			match pattern.strip_prefix("*.") {
+				Some(ext) if isPlain(ext) => {
+					self.suffixes.entry(ext.to_lowercase()).or_default().push(glob);
+				}
+				_ if isPlain(pattern) => {
+					self.names.entry(pattern.to_lowercase()).or_default().push(glob);
+				}
+				_ => {
+					match GlobBuilder::new(pattern).case_insensitive(!caseSensitive).literal_separator(true).build() {
+						Ok(g) => { self.others.push((glob, g.compile_matcher())); }
+						Err(err) => { println!("Bad mime glob {}: {}", pattern, err); }
+					}
+				}
+			}
+		}
+	}
+	
+	/// The highest weight wins, then the longest pattern
+	fn byName(&self, name: &str) -> Option<&str> {
+		let lower = name.to_lowercase();
+		// Lowercasing can change lengths outside of ASCII, the original suffix is then unknown
+		let matches = |g: &&Glob, original: Option<&str>| !g.caseSensitive || original == Some(&g.pattern[2..]);
+		
+		let mut found: Vec<&Glob> = vec![];
+		if let Some(globs) = self.names.get(&lower) {
+			found.extend(globs.iter().filter(|g| !g.caseSensitive || g.pattern == name));
+		}
+		for (i, _) in lower.match_indices('.') {
+			if let Some(globs) = self.suffixes.get(&lower[i + 1..]) {
+				found.extend(globs.iter().filter(|g| matches(g, name.get(i + 1..))));
+			}
+		}
+		if found.is_empty() {
+			found.extend(self.others.iter().filter(|(_, m)| m.is_match(name)).map(|(g, _)| g));
+		}
+		found.into_iter().max_by_key(|g| (g.weight, g.pattern.len())).map(|g| g.mime.as_str())
+	}
+}
+
+/// The mime type of the file from its name, or a look at its content when the name says nothing. Symlinks are followed.
+pub fn forPath(path: &Path) -> String {
+	let meta = match fs::metadata(path) {
+		Ok(m) => { m }
+		Err(_) => { return if fs::symlink_metadata(path).is_ok() { "inode/symlink".to_string() } else { UNKNOWN.to_string() }; }
+	};
+	if meta.is_dir() {
+		return DIRECTORY.to_string();
+	}
+	#[cfg(unix)] {
+		use std::os::unix::fs::FileTypeExt;
+		let t = meta.file_type();
+		let special = if t.is_fifo() { "inode/fifo" } else if t.is_socket() { "inode/socket" } else if t.is_block_device() { "inode/blockdevice" } else if t.is_char_device() { "inode/chardevice" } else { "" };
+		if !special.is_empty() {
+			return special.to_string();
+		}
+	}
+	
+	if let Some(mime) = path.file_name().and_then(|n| DATABASE.byName(&n.to_string_lossy())) {
+		return mime.to_string();
+	}
+	if meta.len() == 0 {
+		return "application/x-zerosize".to_string();
+	}
+	sniff(path).to_string()
+}
+
+/// A few common signatures, text or unknown otherwise
+fn sniff(path: &Path) -> &'static str {
+	let mut head = [0u8; 512];
+	let len = match fs::File::open(path).and_then(|mut f| f.read(&mut head)) {
+		Ok(l) => { l }
+		Err(_) => { return UNKNOWN; }
+	};
+	let head = &head[..len];
+	const MAGIC: [(&[u8], &str); 9] = [
+		(b"\x89PNG", "image/png"),
+		(b"\xff\xd8\xff", "image/jpeg"),
+		(b"GIF8", "image/gif"),
+		(b"%PDF-", "application/pdf"),
+		(b"PK\x03\x04", "application/zip"),
+		(b"\x7fELF", "application/x-executable"),
+		(b"\x1f\x8b", "application/gzip"),
+		(b"#!", "application/x-shellscript"),
+		(b"<?xml", "application/xml"),
+	];
+	if let Some((_, mime)) = MAGIC.iter().find(|(m, _)| head.starts_with(m)) {
+		return mime;
+	}
+	if head.contains(&0) {
+		return UNKNOWN;
+	}
+	match std::str::from_utf8(head) {
+		Ok(_) => { "text/plain" }
+		// The read can end in the middle of a character
+		Err(err) if err.error_len().is_none() => { "text/plain" }
+		Err(_) => { UNKNOWN }
+	}
+}
+
+pub fn unalias(mime: &str) -> String {
+	DATABASE.aliases.get(mime).cloned().unwrap_or_else(|| mime.to_string())
+}
+
+/// Every type the mime type is a kind of, closest first. All text is text/plain, but not everything is application/octet-stream for this.
+pub fn ancestors(mime: &str) -> Vec<String> {
+	let mut res: Vec<String> = vec![];
+	let mut queue = vec![unalias(mime)];
+	while !queue.is_empty() {
+		let current = queue.remove(0);
+		let mut parents = DATABASE.parents.get(&current).cloned().unwrap_or_default();
+		if parents.is_empty() && current.starts_with("text/") && current != "text/plain" {
+			parents.push("text/plain".to_string());
+		}
+		for p in parents.into_iter().map(|p| unalias(&p)) {
+			if p != mime && !res.contains(&p) {
+				res.push(p.clone());
+				queue.push(p);
+			}
+		}
+	}
+	res
+}
@@ -0,0 +1,393 @@
+use std::{fs, process, thread};
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::actions::spawnTerminal;
+use crate::config::{configDirs, configHome, dataDirs};
+use crate::mime;
+use crate::uri::pathToUri;
+
+/// An application from a .desktop file, see https://specifications.freedesktop.org/desktop-entry-spec/latest/
+#[derive(Debug, Clone)]
+pub struct App {
+	/// The desktop file id, like org.gnome.gedit.desktop
+	pub id: String,
+	pub name: String,
+	pub comment: String,
+	pub exec: String,
+	pub icon: String,
+	pub mimeTypes: Vec<String>,
+	/// Only meant for opening files, not for menus
+	pub noDisplay: bool,
+	pub terminal: bool,
+	/// Working directory
+	pub path: Option<PathBuf>,
+	pub file: PathBuf,
+}
+
+/// The applications for a mime type, the default first
+pub struct Choices {
+	pub apps: Vec<App>,
+	pub default: Option<String>,
+}
+
+/// Every installed application, the ones in more important directories hide those with the same id
+pub fn installed() -> Vec<App> {
+	let mut apps: HashMap<String, App> = HashMap::new();
+	for dir in dataDirs().iter().rev().map(|d| d.join("applications")) {
+		let mut files = vec![];
+		collectDesktopFiles(&dir, &mut files);
+		for file in files {
+			let id = file.strip_prefix(&dir).unwrap_or(&file).to_string_lossy().replace('/', "-");
+			let app = fs::read_to_string(&file).ok().and_then(|text| parseEntry(&id, &file, &text));
+			match app {
+				Some(app) => { apps.insert(id, app); }
+				// A hidden entry removes the one from a less important directory
+				None => { apps.remove(&id); }
+			}
+		}
+	}
+	let mut apps: Vec<App> = apps.into_values().collect();
+	apps.sort_by_key(|a| a.name.to_lowercase());
+	apps
+}
+
+pub fn find(id: &str) -> Option<App> {
+	installed().into_iter().find(|a| a.id == id)
+}
+
+fn collectDesktopFiles(dir: &Path, files: &mut Vec<PathBuf>) {
+	let read = match fs::read_dir(dir) {
+		Ok(r) => { r }
+		Err(_) => { return; }
+	};
+	for entry in read.flatten() {
+		let path = entry.path();
+		if path.is_dir() {
+			collectDesktopFiles(&path, files);
+		} else if path.extension().map(|e| e == "desktop").unwrap_or(false) {
+			files.push(path);
+		}
+	}
+}
+
+/// None for entries that are not applications or are hidden
+fn parseEntry(id: &str, file: &Path, text: &str) -> Option<App> {
+	let mut values: HashMap<&str, String> = HashMap::new();
+	let mut inMain = false;
+	for line in text.lines().map(|l| l.trim()) {
+		if line.starts_with('[') {
+			inMain = line == "[Desktop Entry]";
+			continue;
+		}
+		if !inMain || line.starts_with('#') {
+			continue;
+		}
+		if let Some((key, value)) = line.split_once('=') {
+			values.entry(key.trim()).or_insert_with(|| unescape(value.trim()));
+		}
+	}
+	
+	let flag = |key: &str| values.get(key).map(|v| v == "true").unwrap_or(false);
+	if values.get("Type").map(|t| t.as_str()) != Some("Application") || flag("Hidden") {
+		return None;
+	}
+	let exec = values.get("Exec").filter(|e| !e.is_empty())?.clone();
+	let mimeTypes = values.get("MimeType").map(|v| v.split(';').filter(|s| !s.is_empty()).map(mime::unalias).collect()).unwrap_or_default();
+	Some(App {
+		id: id.to_string(),
+		name: values.get("Name").cloned().unwrap_or_else(|| id.trim_end_matches(".desktop").to_string()),
+		comment: values.get("Comment").cloned().unwrap_or_default(),
+		exec,
+		icon: values.get("Icon").cloned().unwrap_or_default(),
+		mimeTypes,
+		noDisplay: flag("NoDisplay"),
+		terminal: flag("Terminal"),
+		path: values.get("Path").filter(|p| !p.is_empty()).map(PathBuf::from),
+		file: file.to_path_buf(),
+	})
+}
+
+/// The escapes of string values, \s \n \t \r and \\
+fn unescape(value: &str) -> String {
+	let mut res = String::with_capacity(value.len());
+	let mut chars = value.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			res.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('s') => { res.push(' '); }
+			Some('n') => { res.push('\n'); }
+			Some('t') => { res.push('\t'); }
+			Some('r') => { res.push('\r'); }
+			Some(other) => { res.push(other); }
+			None => { res.push('\\'); }
+		}
+	}
+	res
+}
+
+/// The groups of one mimeapps.list, see https://specifications.freedesktop.org/mime-apps-spec/latest/
+#[derive(Default)]
+struct MimeApps {
+	defaults: HashMap<String, Vec<String>>,
+	added: HashMap<String, Vec<String>>,
+	removed: HashMap<String, Vec<String>>,
+}
+
+fn readMimeApps(file: &Path) -> Option<MimeApps> {
+	let text = fs::read_to_string(file).ok()?;
+	let mut res = MimeApps::default();
+	let mut group = "";
+	for line in text.lines().map(|l| l.trim()) {
+		if line.starts_with('[') {
+			group = line;
+			continue;
+		}
+		let target = match group {
+			"[Default Applications]" => { &mut res.defaults }
+			"[Added Associations]" => { &mut res.added }
+			"[Removed Associations]" => { &mut res.removed }
+			_ => { continue; }
+		};
+		if let Some((key, value)) = line.split_once('=') {
+			let ids = value.split(';').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+			target.insert(mime::unalias(key.trim()), ids);
+		}
+	}
+	Some(res)
+}
+
+/// The names of the running desktop, most specific first
+fn currentDesktops() -> Vec<String> {
+	env::var("XDG_CURRENT_DESKTOP").unwrap_or_default()
+	                               .split(':').filter(|d| !d.is_empty()).map(|d| d.to_lowercase()).collect()
+}
+
+/// Every mimeapps.list that applies, most important first
+fn mimeAppsFiles() -> Vec<PathBuf> {
+	let desktops = currentDesktops();
+	let mut dirs = configDirs();
+	dirs.extend(dataDirs().into_iter().map(|d| d.join("applications")));
+	
+	let mut files = vec![];
+	for dir in dirs {
+		for desktop in &desktops {
+			files.push(dir.join(format!("{}-mimeapps.list", desktop)));
+		}
+		files.push(dir.join("mimeapps.list"));
+	}
+	files
+}
+
+/// The default and the other applications for the type, followed by those for its parent types like text/plain
+pub fn appsFor(mimeType: &str) -> Choices {
+	let installed = installed();
+	let byId: HashMap<&str, &App> = installed.iter().map(|a| (a.id.as_str(), a)).collect();
+	let lists: Vec<MimeApps> = mimeAppsFiles().iter().filter_map(|f| readMimeApps(f)).collect();
+	
+	let mut types = vec![mime::unalias(mimeType)];
+	types.extend(mime::ancestors(mimeType));
+	
+	let mut default = None;
+	let mut ids: Vec<&str> = vec![];
+	for t in &types {
+		let removed = |id: &str| lists.iter().any(|l| l.removed.get(t).map(|r| r.iter().any(|r| r == id)).unwrap_or(false));
+		if default.is_none() {
+			default = lists.iter().filter_map(|l| l.defaults.get(t)).flatten()
+			               .find(|id| byId.contains_key(id.as_str()))
+			               .cloned();
+		}
+		let added = lists.iter().filter_map(|l| l.added.get(t)).flatten().map(|id| id.as_str());
+		let listed = installed.iter().filter(|a| a.mimeTypes.contains(t)).map(|a| a.id.as_str());
+		for id in added.chain(listed) {
+			if byId.contains_key(id) && !removed(id) && !ids.contains(&id) {
+				ids.push(id);
+			}
+		}
+	}
+	if let Some(d) = &default {
+		ids.retain(|id| id != d);
+		ids.insert(0, d);
+	}
+	
+	Choices { apps: ids.into_iter().map(|id| byId[id].clone()).collect(), default }
+}
+
+/// Writes the default into the mimeapps.list of the user, keeping everything else in it.
+/// A list for the running desktop would win over mimeapps.list, so when the user has one the default goes there.
+pub fn setDefault(mimeType: &str, id: &str) -> Result<(), String> {
+	let dir = configHome().ok_or("No home directory")?;
+	let file = currentDesktops().into_iter()
+	                            .map(|d| dir.join(format!("{}-mimeapps.list", d)))
+	                            .find(|f| f.is_file())
+	                            .unwrap_or_else(|| dir.join("mimeapps.list"));
+	let text = fs::read_to_string(&file).unwrap_or_default();
+	let mut lines: Vec<String> = text.lines().map(|l| l.to_string()).collect();
+	
+	setKey(&mut lines, "[Default Applications]", mimeType, |_| format!("{};", id));
+	setKey(&mut lines, "[Added Associations]", mimeType, |old| {
+		let mut ids = vec![id];
+		ids.extend(old.split(';').map(|s| s.trim()).filter(|s| !s.is_empty() && *s != id));
+		ids.iter().map(|i| format!("{};", i)).collect()
+	});
+	
+	if let Some(dir) = file.parent() {
+		fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+	}
+	fs::write(&file, lines.join("\n") + "\n").map_err(|err| format!("Failed to save {}: {}", file.display(), err))
+}
+
+/// Replaces the value of the key in the group, adding the group and key if missing. The new value is made from the old one.
+fn setKey<F: Fn(&str) -> String>(lines: &mut Vec<String>, group: &str, key: &str, value: F) {
+	let start = match lines.iter().position(|l| l.trim() == group) {
+		Some(i) => { i + 1 }
+		None => {
+			if lines.last().map(|l| !l.trim().is_empty()).unwrap_or(false) {
+				lines.push(String::new());
+			}
+			lines.push(group.to_string());
+			lines.len()
+		}
+	};
+	let end = lines[start..].iter().position(|l| l.trim().starts_with('[')).map(|i| i + start).unwrap_or(lines.len());
+	
+	for line in &mut lines[start..end] {
+		if let Some((k, old)) = line.split_once('=') {
+			if k.trim() == key {
+				*line = format!("{}={}", key, value(old));
+				return;
+			}
+		}
+	}
+	// After the last entry of the group, not after the blank lines that end it
+	let mut at = end;
+	while at > start && lines[at - 1].trim().is_empty() {
+		at -= 1;
+	}
+	lines.insert(at, format!("{}={}", key, value("")));
+}
+
+/// Splits Exec into arguments, which can be quoted with "" and escape ", `, $ and \ inside of quotes
+fn splitExec(exec: &str) -> Result<Vec<String>, String> {
+	let mut args = vec![];
+	let mut current = String::new();
+	let mut inArg = false;
+	let mut chars = exec.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'"' => {
+				inArg = true;
+				loop {
+					match chars.next() {
+						None => { return Err("A quote in Exec is not closed".to_string()); }
+						Some('"') => { break; }
+						Some('\\') => {
+							match chars.next() {
+								Some(e @ ('"' | '`' | '$' | '\\')) => { current.push(e); }
+								Some(e) => {
+									current.push('\\');
+									current.push(e);
+								}
+								None => { return Err("A quote in Exec is not closed".to_string()); }
+							}
+						}
+						Some(other) => { current.push(other); }
+					}
+				}
+			}
+			' ' | '\t' => {
+				if inArg {
+					args.push(std::mem::take(&mut current));
+					inArg = false;
+				}
+			}
+			_ => {
+				current.push(c);
+				inArg = true;
+			}
+		}
+	}
+	if inArg {
+		args.push(current);
+	}
+	Ok(args)
+}
+
+/// The commands to run, one for all files when Exec takes lists (%F, %U), one per file when it takes single files (%f, %u)
+fn commands(app: &App, files: &[PathBuf]) -> Result<Vec<Vec<String>>, String> {
+	let args = splitExec(&app.exec)?;
+	let takesList = args.iter().any(|a| a == "%F" || a == "%U");
+	let takesOne = args.iter().any(|a| a.contains("%f") || a.contains("%u"));
+	let groups: Vec<&[PathBuf]> = if takesList || !takesOne || files.is_empty() {
+		vec![files]
+	} else {
+		files.chunks(1).collect()
+	};
+	
+	let mut res = vec![];
+	for group in groups {
+		let mut command = vec![];
+		for arg in &args {
+			match arg.as_str() {
+				"%F" => { command.extend(group.iter().map(|f| f.to_string_lossy().into_owned())); }
+				"%U" => { command.extend(group.iter().map(|f| pathToUri(f))); }
+				"%i" => {
+					if !app.icon.is_empty() {
+						command.push("--icon".to_string());
+						command.push(app.icon.clone());
+					}
+				}
+				_ => {
+					let mut expanded = String::new();
+					let mut chars = arg.chars();
+					while let Some(c) = chars.next() {
+						if c != '%' {
+							expanded.push(c);
+							continue;
+						}
+						match chars.next() {
+							Some('%') => { expanded.push('%'); }
+							Some('f') => { expanded += &group.first().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default(); }
+							Some('u') => { expanded += &group.first().map(|f| pathToUri(f)).unwrap_or_default(); }
+							Some('c') => { expanded += &app.name; }
+							Some('k') => { expanded += &app.file.to_string_lossy(); }
+							// Deprecated and unknown codes are dropped
+							_ => {}
+						}
+					}
+					// An argument that was only a file code for no file disappears
+					if !expanded.is_empty() || !arg.starts_with('%') {
+						command.push(expanded);
+					}
+				}
+			}
+		}
+		if command.is_empty() {
+			return Err(format!("{} has no command", app.name));
+		}
+		res.push(command);
+	}
+	Ok(res)
+}
+
+/// Starts the application with the files, in a terminal if it asks for one
+pub fn launch(app: &App, files: &[PathBuf]) -> Result<(), String> {
+	let dir = app.path.clone()
+	             .or_else(|| files.first().and_then(|f| f.parent()).map(|p| p.to_path_buf()))
+	             .unwrap_or_else(|| PathBuf::from("."));
+	for command in commands(app, files)? {
+		if app.terminal {
+			spawnTerminal(&dir, &command)?;
+			continue;
+		}
+		let mut child = process::Command::new(&command[0]).args(&command[1..]).current_dir(&dir).spawn()
+		                                                  .map_err(|err| format!("Failed to start {}: {}", app.name, err))?;
+		// Collects the exit status so the process does not stay around as a zombie
+		thread::spawn(move || child.wait());
+	}
+	Ok(())
+}
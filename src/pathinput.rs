@@ -0,0 +1,120 @@
+use std::{env, fs};
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+
+const MAX_COMPLETIONS: usize = 30;
+
+/// Replaces a leading ~ with the home directory and $VAR or ${VAR} with the value of the variable. Unknown variables are left alone.
+pub fn expand(text: &str) -> String {
+	let text = text.trim();
+	let mut res = String::with_capacity(text.len());
+	
+	let rest = match home::home_dir() {
+		Some(home) if text == "~" || text.starts_with("~/") || text.starts_with(&format!("~{}", MAIN_SEPARATOR)) => {
+			res.push_str(&home.to_string_lossy());
+			&text[1..]
+		}
+		_ => { text }
+	};
+	
+	let mut chars = rest.char_indices().peekable();
+	while let Some((i, c)) = chars.next() {
+		if c != '$' {
+			res.push(c);
+			continue;
+		}
+		
+		let braced = matches!(chars.peek(), Some((_, '{')));
+		let start = if braced { chars.next(); i + 2 } else { i + 1 };
+		let mut end = start;
+		while let Some((j, c)) = chars.peek().copied() {
+			if !(c.is_alphanumeric() || c == '_') { break; }
+			end = j + c.len_utf8();
+			chars.next();
+		}
+		let closed = !braced || matches!(chars.peek(), Some((_, '}')));
+		
+		match env::var(&rest[start..end]) {
+			Ok(value) if end > start && closed => {
+				if braced { chars.next(); }
+				res.push_str(&value);
+			}
+			_ => {
+				res.push_str(&rest[i..end]);
+			}
+		}
+	}
+	res
+}
+
+/// Directories whose path starts with the (expanded) text, compared case insensitively. Hidden directories are only offered once the name starts with a dot.
+pub fn complete(text: &str) -> Vec<PathBuf> {
+	let text = expand(text);
+	let (dir, prefix) = match text.rfind(['/', MAIN_SEPARATOR]) {
+		None => { return vec![]; }
+		Some(i) => { (&text[..=i], text[i + 1..].to_lowercase()) }
+	};
+	
+	let read = match fs::read_dir(dir) {
+		Ok(r) => { r }
+		Err(_) => { return vec![]; }
+	};
+	let mut res: Vec<PathBuf> = read.flatten()
+	                                .filter(|e| e.path().is_dir())
+	                                .filter(|e| {
+		                                let name = e.file_name().to_string_lossy().to_lowercase();
+		                                name.starts_with(&prefix) && (prefix.starts_with('.') || !name.starts_with('.'))
+	                                })
+	                                .map(|e| Path::new(dir).join(e.file_name()))
+	                                .collect();
+	res.sort_by_key(|p| p.to_string_lossy().to_lowercase());
+	res.truncate(MAX_COMPLETIONS);
+	res
+}
+
+/// Subdirectories of dir by name, for the breadcrumb dropdowns
+pub fn subdirectories(dir: &str, showHidden: bool) -> Vec<PathBuf> {
+	let read = match fs::read_dir(dir) {
+		Ok(r) => { r }
+		Err(_) => { return vec![]; }
+	};
+	let mut res: Vec<PathBuf> = read.flatten()
+	                                .filter(|e| showHidden || !e.file_name().to_string_lossy().starts_with('.'))
+	                                .map(|e| e.path())
+	                                .filter(|p| p.is_dir())
+	                                .collect();
+	res.sort_by_key(|p| p.to_string_lossy().to_lowercase());
+	res
+}
+
+/// What tab completion turns the text into: the only candidate, or as much as all candidates have in common
+pub fn completeCommon(text: &str) -> String {
+	let candidates = complete(text);
+	match candidates.len() {
+		0 => { text.to_string() }
+		1 => { format!("{}{}", candidates[0].to_string_lossy(), MAIN_SEPARATOR) }
+		_ => {
+			let mut common: Vec<char> = candidates[0].to_string_lossy().chars().collect();
+			for c in &candidates[1..] {
+				let same = common.iter().zip(c.to_string_lossy().chars()).take_while(|(a, b)| a.to_lowercase().eq(b.to_lowercase())).count();
+				common.truncate(same);
+			}
+			// Only the case of what was typed would change, that is not worth replacing the text for
+			if common.len() <= expand(text).chars().count() {
+				return text.to_string();
+			}
+			common.into_iter().collect()
+		}
+	}
+}
+
+/// Empty if the text points at something that exists, otherwise what is wrong with it
+pub fn validate(text: &str) -> String {
+	let text = expand(text);
+	if text.is_empty() {
+		return "Empty path".to_string();
+	}
+	match fs::metadata(&text) {
+		Ok(_) => { String::new() }
+		Err(err) => { err.to_string() }
+	}
+}
@@ -0,0 +1,103 @@
+use std::{fs, io};
+use std::path::Path;
+
+use crate::datetime::{Civil, formatTime};
+
+/// What the EXIF data of a photo says about it
+#[derive(Debug, Clone, Default)]
+pub struct PhotoInfo {
+	/// 1 to 8 like the EXIF tag, 1 is upright
+	pub orientation: u32,
+	pub make: String,
+	pub model: String,
+	/// In the local time of the camera
+	pub taken: Option<Civil>,
+	/// Latitude and longitude in degrees, south and west are negative
+	pub location: Option<(f64, f64)>,
+	/// The small JPEG cameras embed for previews
+	pub thumbnail: Option<Vec<u8>>,
+}
+
+impl PhotoInfo {
+	/// Make and model, without the make twice as in "Canon Canon EOS 80D"
+	pub fn camera(&self) -> String {
+		if self.make.is_empty() || self.model.to_lowercase().starts_with(&self.make.to_lowercase()) {
+			self.model.clone()
+		} else {
+			format!("{} {}", self.make, self.model)
+		}
+	}
+	
+	/// Camera, date and location on their own lines, the ones that are known
+	pub fn describe(&self) -> String {
+		let mut lines = vec![];
+		let camera = self.camera();
+		if !camera.is_empty() {
+			lines.push(camera);
+		}
+		if let Some(taken) = self.taken {
+			lines.push(formatTime(taken, "%Y-%m-%d %H:%M"));
+		}
+		if let Some((lat, lon)) = self.location {
+			lines.push(format!("{:.5}° {}, {:.5}° {}", lat.abs(), if lat < 0.0 { "S" } else { "N" }, lon.abs(), if lon < 0.0 { "W" } else { "E" }));
+		}
+		lines.join("\n")
+	}
+	
+	/// Whether width and height are swapped by the orientation
+	pub fn isTransposed(&self) -> bool {
+		self.orientation >= 5
+	}
+}
+
+/// Reads the EXIF data of JPEG, TIFF, PNG, WebP and HEIF files, None for other files and photos without it
+pub fn read(path: &Path) -> Option<PhotoInfo> {
+	let file = fs::File::open(path).ok()?;
+	let exif = exif::Reader::new().read_from_container(&mut io::BufReader::new(file)).ok()?;
+	
+	let ascii = |tag| match exif.get_field(tag, exif::In::PRIMARY).map(|f| &f.value) {
+		Some(exif::Value::Ascii(v)) if !v.is_empty() => { Some(String::from_utf8_lossy(&v[0]).trim().to_string()) }
+		_ => { None }
+	};
+	let uint = |tag, ifd| exif.get_field(tag, ifd).and_then(|f| f.value.get_uint(0));
+	
+	let taken = ascii(exif::Tag::DateTimeOriginal).or_else(|| ascii(exif::Tag::DateTime)).and_then(|s| parseExifDate(&s));
+	
+	let degrees = |tag, refTag, negative: &str| {
+		let value = match exif.get_field(tag, exif::In::PRIMARY).map(|f| &f.value) {
+			Some(exif::Value::Rational(v)) if v.len() == 3 => { v[0].to_f64() + v[1].to_f64() / 60.0 + v[2].to_f64() / 3600.0 }
+			_ => { return None; }
+		};
+		Some(if ascii(refTag).as_deref() == Some(negative) { -value } else { value })
+	};
+	let location = match (degrees(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, "S"), degrees(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, "W")) {
+		(Some(lat), Some(lon)) if lat.is_finite() && lon.is_finite() => { Some((lat, lon)) }
+		_ => { None }
+	};
+	
+	// The offset of the thumbnail counts from the start of the TIFF data, which is what buf holds. Both come from the file, so they may be anything.
+	let thumbnail = match (uint(exif::Tag::JPEGInterchangeFormat, exif::In::THUMBNAIL), uint(exif::Tag::JPEGInterchangeFormatLength, exif::In::THUMBNAIL)) {
+		(Some(start), Some(len)) => {
+			(start as usize).checked_add(len as usize).and_then(|end| exif.buf().get(start as usize..end)).map(|b| b.to_vec())
+		}
+		_ => { None }
+	};
+	
+	Some(PhotoInfo {
+		orientation: uint(exif::Tag::Orientation, exif::In::PRIMARY).filter(|o| (1..=8).contains(o)).unwrap_or(1),
+		make: ascii(exif::Tag::Make).unwrap_or_default(),
+		model: ascii(exif::Tag::Model).unwrap_or_default(),
+		taken,
+		location,
+		thumbnail,
+	})
+}
+
+/// EXIF dates look like 2023:04:05 12:34:56 in the local time of the camera
+pub fn parseExifDate(text: &str) -> Option<Civil> {
+	let parts: Vec<i64> = text.split([':', ' ']).map(|p| p.parse().ok()).collect::<Option<_>>()?;
+	if parts.len() != 6 || parts[0] == 0 {
+		return None;
+	}
+	Some(Civil([parts[0], parts[1], parts[2], parts[3], parts[4], parts[5]]))
+}
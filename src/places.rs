@@ -0,0 +1,199 @@
+use std::{env, fs, thread};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use crate::bookmarks;
+use crate::config::configHome;
+use crate::bookmarks::Bookmark;
+
+/// How often the bookmarks and mounted filesystems are checked for changes
+const REFRESH_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+	pub label: String,
+	pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+	pub label: String,
+	pub path: PathBuf,
+	pub free: u64,
+	pub total: u64,
+}
+
+/// Everything the sidebar shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Places {
+	pub userDirs: Vec<Place>,
+	pub bookmarks: Vec<Place>,
+	pub mounts: Vec<Mount>,
+}
+
+pub fn snapshot() -> Places {
+	Places {
+		userDirs: userDirs(),
+		bookmarks: bookmarks::read().into_iter().map(|Bookmark { path, label }| Place { label, path }).collect(),
+		mounts: mounts(),
+	}
+}
+
+/// Sends a snapshot right away and then every time something changed, until the receiver is gone
+pub fn watch(send: Sender<Places>) {
+	thread::Builder::new().name("Places".into()).spawn(move || {
+		let mut last = None;
+		loop {
+			let places = snapshot();
+			if last.as_ref() != Some(&places) {
+				if send.send(places.clone()).is_err() {
+					return;
+				}
+				last = Some(places);
+			}
+			thread::sleep(REFRESH_INTERVAL);
+		}
+	}).map_err(|err| println!("Failed to start watching places: {}", err)).ok();
+}
+
+/// Home followed by the XDG user directories (Desktop, Downloads, ...) that exist
+pub fn userDirs() -> Vec<Place> {
+	let home = match home::home_dir() {
+		None => { return vec![]; }
+		Some(h) => { h }
+	};
+	let mut res = vec![Place { label: "Home".to_string(), path: home.clone() }];
+	
+	for (key, label) in [("DESKTOP", "Desktop"), ("DOCUMENTS", "Documents"), ("DOWNLOAD", "Downloads"), ("MUSIC", "Music"),
+	                     ("PICTURES", "Pictures"), ("VIDEOS", "Videos"), ("PUBLICSHARE", "Public"), ("TEMPLATES", "Templates")] {
+		if let Some(path) = userDir(key) {
+			if path != home && path.is_dir() {
+				res.push(Place { label: label.to_string(), path });
+			}
+		}
+	}
+	res
+}
+
+/// Reads XDG_{key}_DIR from the environment or user-dirs.dirs, see https://www.freedesktop.org/wiki/Software/xdg-user-dirs/
+pub fn userDir(key: &str) -> Option<PathBuf> {
+	let name = format!("XDG_{}_DIR", key);
+	if let Some(dir) = env::var_os(&name) {
+		return Some(PathBuf::from(dir));
+	}
+	
+	let home = home::home_dir()?;
+	let data = fs::read_to_string(configHome()?.join("user-dirs.dirs")).ok()?;
+	
+	data.lines().find_map(|line| {
+		let value = line.trim().strip_prefix(&name)?.trim_start().strip_prefix('=')?.trim().trim_matches('"');
+		Some(match value.strip_prefix("$HOME") {
+			None => { PathBuf::from(value) }
+			Some(rest) => { home.join(rest.trim_start_matches('/')) }
+		})
+	})
+}
+
+/// Mounted filesystems that hold user data, pseudo filesystems like proc or cgroup are left out
+#[cfg(target_os = "linux")]
+pub fn mounts() -> Vec<Mount> {
+	let data = match fs::read_to_string("/proc/self/mountinfo") {
+		Ok(d) => { d }
+		Err(_) => { return vec![]; }
+	};
+	
+	let mut res: Vec<Mount> = vec![];
+	for line in data.lines() {
+		// id parent major:minor root mountpoint options [optional fields] - fstype source superoptions
+		let (left, right) = match line.split_once(" - ") {
+			None => { continue; }
+			Some(s) => { s }
+		};
+		let mountPoint = match left.split(' ').nth(4) {
+			None => { continue; }
+			Some(m) => { unescapeMountPath(m) }
+		};
+		let mut right = right.split(' ');
+		let (fsType, source) = match (right.next(), right.next()) {
+			(Some(t), Some(s)) => { (t, unescapeMountPath(s)) }
+			_ => { continue; }
+		};
+		
+		let network = matches!(fsType, "nfs" | "nfs4" | "cifs" | "smb3" | "sshfs" | "fuse.sshfs");
+		if !(source.starts_with("/dev/") || network) || mountPoint.starts_with("/boot") || mountPoint.starts_with("/snap/") {
+			continue;
+		}
+		// Bind mounts and btrfs subvolumes show up once per mount point, only the first one is interesting
+		if res.iter().any(|m| m.path == Path::new(&mountPoint)) {
+			continue;
+		}
+		
+		let (free, total) = match space(&mountPoint) {
+			Some(s) if s.1 > 0 => { s }
+			_ => { continue; }
+		};
+		let label = if mountPoint == "/" {
+			"File system".to_string()
+		} else {
+			PathBuf::from(&mountPoint).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| mountPoint.clone())
+		};
+		res.push(Mount { label, path: PathBuf::from(mountPoint), free, total });
+	}
+	res
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn mounts() -> Vec<Mount> {
+	vec![]
+}
+
+/// Spaces and other special characters are written as octal escapes like \040
+fn unescapeMountPath(path: &str) -> String {
+	let bytes = path.as_bytes();
+	let mut res = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+			res.push((bytes[i + 1] - b'0') << 6 | (bytes[i + 2] - b'0') << 3 | (bytes[i + 3] - b'0'));
+			i += 4;
+			continue;
+		}
+		res.push(bytes[i]);
+		i += 1;
+	}
+	String::from_utf8_lossy(&res).into_owned()
+}
+
+/// Free (for unprivileged users) and total bytes
+#[cfg(unix)]
+pub fn space(path: &str) -> Option<(u64, u64)> {
+	let cPath = std::ffi::CString::new(path).ok()?;
+	let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+	if unsafe { libc::statvfs(cPath.as_ptr(), &mut stat) } != 0 {
+		return None;
+	}
+	let block = stat.f_frsize as u64;
+	Some((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block))
+}
+
+#[cfg(not(unix))]
+pub fn space(_path: &str) -> Option<(u64, u64)> {
+	None
+}
+
+/// Like 1.5 GB, with powers of 1024
+pub fn humanSize(bytes: u64) -> String {
+	const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
+	let mut size = bytes as f64;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	if unit == 0 || size >= 100.0 {
+		format!("{:.0} {}", size, UNITS[unit])
+	} else {
+		format!("{:.1} {}", size, UNITS[unit])
+	}
+}
@@ -0,0 +1,236 @@
+use std::{fs, io, thread};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::UNIX_EPOCH;
+
+use image::ImageFormat;
+
+use crate::datetime::{formatTime, localTime};
+use crate::highlight::{self, Style};
+use crate::{media, mime, photo};
+use crate::places::humanSize;
+use crate::rgba_img::RgbImg;
+
+/// Images are scaled down to this, which is plenty for a side pane
+pub const IMAGE_SIZE: u32 = 1600;
+/// How much of a text file is read and highlighted
+const TEXT_BYTES: usize = 256 * 1024;
+const TEXT_LINES: usize = 2000;
+/// Longer lines are cut, nobody reads them in a preview
+const LINE_CHARS: usize = 500;
+const HEX_BYTES: usize = 4096;
+const ARCHIVE_ENTRIES: usize = 1000;
+
+pub enum Content {
+	Message(String),
+	Image(RgbImg),
+	Text(Vec<Vec<(Style, String)>>),
+	/// Hex dumps and archive listings, shown like text without colors
+	Lines(Vec<String>),
+}
+
+pub struct Preview {
+	pub path: PathBuf,
+	pub content: Content,
+	/// Label and value pairs shown below the content
+	pub metadata: Vec<(String, String)>,
+}
+
+/// Loads the preview in the background. Dropping the receiver before it arrives simply discards it.
+pub fn load(path: PathBuf) -> Receiver<Preview> {
+	let (send, receiver) = channel();
+	thread::spawn(move || {
+		let _ = send.send(read(path));
+	});
+	receiver
+}
+
+fn read(path: PathBuf) -> Preview {
+	let meta = match fs::metadata(&path) {
+		Ok(m) => { m }
+		Err(err) => { return Preview { path, content: Content::Message(err.to_string()), metadata: vec![] }; }
+	};
+	let kind = mime::forPath(&path);
+	let mut metadata = vec![("Type".to_string(), kind.clone())];
+	if !meta.is_dir() {
+		metadata.push(("Size".to_string(), humanSize(meta.len())));
+	}
+	if let Some(modified) = meta.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+		metadata.push(("Modified".to_string(), formatTime(localTime(modified.as_secs() as i64), "%Y-%m-%d %H:%M")));
+	}
+	
+	let content = if meta.is_dir() {
+		match fs::read_dir(&path) {
+			Ok(entries) => {
+				let count = entries.count();
+				Content::Message(if count == 1 { "1 item".to_string() } else { format!("{} items", count) })
+			}
+			Err(err) => { Content::Message(err.to_string()) }
+		}
+	} else if meta.len() == 0 {
+		Content::Message("Empty file".to_string())
+	} else if ImageFormat::from_path(&path).is_ok() && kind.starts_with("image/") {
+		match RgbImg::readSizeLimited(&path.to_string_lossy(), Some(IMAGE_SIZE)) {
+			Ok(img) => {
+				if let Ok((w, h)) = image::image_dimensions(&path) {
+					// Photos are shown upright, so their size is given that way too
+					let transposed = photo::read(&path).map(|p| p.isTransposed()).unwrap_or(false);
+					let (w, h) = if transposed { (h, w) } else { (w, h) };
+					metadata.push(("Dimensions".to_string(), format!("{} × {}", w, h)));
+				}
+				metadata.extend(exifFields(&path));
+				Content::Image(img)
+			}
+			Err(err) => { Content::Message(format!("Failed to read the image: {}", err)) }
+		}
+	} else if kind == "application/zip" || mime::ancestors(&kind).iter().any(|m| m == "application/zip") {
+		match archive(&path, &mut metadata) {
+			Ok(lines) => { Content::Lines(lines) }
+			Err(err) => { Content::Message(err) }
+		}
+	} else if let Some(duration) = media::duration(&path) {
+		metadata.push(("Duration".to_string(), media::describe(duration)));
+		Content::Message(String::new())
+	} else {
+		match head(&path, TEXT_BYTES.max(HEX_BYTES)) {
+			Err(err) => { Content::Message(err.to_string()) }
+			Ok(data) => {
+				match asText(&data) {
+					Some(text) => {
+						let complete = data.len() < TEXT_BYTES;
+						let lines = textLines(&path, text, complete, &mut metadata);
+						Content::Text(lines)
+					}
+					None => { Content::Lines(hexDump(&data[..data.len().min(HEX_BYTES)])) }
+				}
+			}
+		}
+	};
+	Preview { path, content, metadata }
+}
+
+fn head(path: &Path, len: usize) -> io::Result<Vec<u8>> {
+	let mut data = vec![];
+	fs::File::open(path)?.take(len as u64).read_to_end(&mut data)?;
+	Ok(data)
+}
+
+/// None for binary data. The read may have cut the last character in half.
+fn asText(data: &[u8]) -> Option<&str> {
+	if data.contains(&0) {
+		return None;
+	}
+	match std::str::from_utf8(data) {
+		Ok(t) => { Some(t) }
+		Err(err) if err.error_len().is_none() => { std::str::from_utf8(&data[..err.valid_up_to()]).ok() }
+		Err(_) => { None }
+	}
+}
+
+fn textLines(path: &Path, text: &str, complete: bool, metadata: &mut Vec<(String, String)>) -> Vec<Vec<(Style, String)>> {
+	let total = text.lines().count();
+	let shown = if total > TEXT_LINES { text.lines().take(TEXT_LINES).map(|l| format!("{}\n", l)).collect::<String>() } else { text.to_string() };
+	// Tabs have no width in a Text element
+	let shown = shown.replace('\t', "    ");
+	if complete {
+		metadata.push(("Lines".to_string(), total.to_string()));
+	}
+	if !complete || total > TEXT_LINES {
+		metadata.push(("Preview".to_string(), format!("First {} lines", total.min(TEXT_LINES))));
+	}
+	
+	let mut lines = highlight::highlight(&shown, highlight::languageFor(path));
+	for line in &mut lines {
+		let mut left = LINE_CHARS;
+		line.retain_mut(|(_, t)| {
+			if left == 0 {
+				return false;
+			}
+			if let Some((cut, _)) = t.char_indices().nth(left) {
+				t.truncate(cut);
+				t.push('…');
+			}
+			left = left.saturating_sub(t.chars().count());
+			true
+		});
+	}
+	lines
+}
+
+/// Offset, 16 bytes in hex and the same bytes as ASCII, like hexdump -C
+fn hexDump(data: &[u8]) -> Vec<String> {
+	data.chunks(16).enumerate().map(|(i, row)| {
+		let mut line = format!("{:08x} ", i * 16);
+		for j in 0..16 {
+			if j == 8 {
+				line.push(' ');
+			}
+			match row.get(j) {
+				Some(b) => { line += &format!(" {:02x}", b); }
+				None => { line += "   "; }
+			}
+		}
+		let ascii: String = row.iter().map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' }).collect();
+		line + "  |" + &ascii + "|"
+	}).collect()
+}
+
+fn archive(path: &Path, metadata: &mut Vec<(String, String)>) -> Result<Vec<String>, String> {
+	let file = fs::File::open(path).map_err(|err| err.to_string())?;
+	let mut zip = zip::ZipArchive::new(io::BufReader::new(file)).map_err(|err| format!("Failed to read the archive: {}", err))?;
+	let mut lines = vec![];
+	let mut unpacked = 0;
+	for i in 0..zip.len() {
+		let entry = match zip.by_index_raw(i) {
+			Ok(e) => { e }
+			Err(_) => { continue; }
+		};
+		unpacked += entry.size();
+		if i < ARCHIVE_ENTRIES {
+			let size = if entry.is_dir() { String::new() } else { humanSize(entry.size()) };
+			lines.push(format!("{:>10}  {}", size, entry.name()));
+		}
+	}
+	if zip.len() > ARCHIVE_ENTRIES {
+		lines.push(format!("… and {} more", zip.len() - ARCHIVE_ENTRIES));
+	}
+	metadata.push(("Entries".to_string(), zip.len().to_string()));
+	metadata.push(("Unpacked".to_string(), humanSize(unpacked)));
+	Ok(lines)
+}
+
+/// The interesting part of the EXIF data of photos
+fn exifFields(path: &Path) -> Vec<(String, String)> {
+	let exif = match fs::File::open(path).ok().and_then(|f| exif::Reader::new().read_from_container(&mut io::BufReader::new(f)).ok()) {
+		None => { return vec![]; }
+		Some(e) => { e }
+	};
+	let field = |tag| exif.get_field(tag, exif::In::PRIMARY).map(|f| f.display_value().with_unit(&exif).to_string().trim_matches('"').trim().to_string())
+	                       .filter(|v| !v.is_empty());
+	
+	let mut res = vec![];
+	let camera = [field(exif::Tag::Make), field(exif::Tag::Model)].into_iter().flatten().collect::<Vec<_>>().join(" ");
+	if !camera.is_empty() {
+		res.push(("Camera".to_string(), camera));
+	}
+	let tags = [
+		("Taken", exif::Tag::DateTimeOriginal),
+		("Lens", exif::Tag::LensModel),
+		("Exposure", exif::Tag::ExposureTime),
+		("Aperture", exif::Tag::FNumber),
+		("ISO", exif::Tag::PhotographicSensitivity),
+		("Focal length", exif::Tag::FocalLength),
+	];
+	for (label, tag) in tags {
+		if let Some(v) = field(tag) {
+			res.push((label.to_string(), v));
+		}
+	}
+	if let (Some(lat), Some(lon)) = (field(exif::Tag::GPSLatitude), field(exif::Tag::GPSLongitude)) {
+		let latRef = field(exif::Tag::GPSLatitudeRef).unwrap_or_default();
+		let lonRef = field(exif::Tag::GPSLongitudeRef).unwrap_or_default();
+		res.push(("Location".to_string(), format!("{} {}, {} {}", lat, latRef, lon, lonRef)));
+	}
+	res
+}
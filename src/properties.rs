@@ -0,0 +1,357 @@
+use std::{fs, thread};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::datetime::{formatTime, localTime};
+use crate::mime;
+
+/// How often the running count is reported
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Totals {
+	pub bytes: u64,
+	/// Allocated blocks, which differs from bytes for sparse and small files
+	pub onDisk: u64,
+	pub files: u64,
+	/// Only the ones below the selected items in a count, the selected folders are not their own content
+	pub dirs: u64,
+	/// Folders that could not be read, their content is missing from the totals
+	pub unreadable: u64,
+}
+
+pub enum SizeEvent {
+	Progress(Totals),
+	Done(Totals),
+}
+
+/// What the properties view shows, ready to display
+pub struct Summary {
+	pub title: String,
+	pub path: String,
+	pub kind: String,
+	pub linkTarget: String,
+	pub modified: String,
+	pub accessed: String,
+	pub created: String,
+	pub owner: String,
+	pub group: String,
+	/// Permission bits of the first item, None where there are none
+	pub mode: Option<u32>,
+	pub hasDirs: bool,
+	/// Only the selected items, nothing below them
+	pub direct: Totals,
+}
+
+pub fn summarize(paths: &[PathBuf]) -> Summary {
+	let metas: Vec<Option<fs::Metadata>> = paths.iter().map(|p| fs::symlink_metadata(p).ok()).collect();
+	let mut direct = Totals::default();
+	for meta in metas.iter().flatten() {
+		if meta.is_dir() {
+			direct.dirs += 1;
+		} else {
+			direct.files += 1;
+			direct.bytes += meta.len();
+		}
+	}
+	
+	let name = |p: &Path| p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| p.to_string_lossy().into_owned());
+	let (title, path) = match paths {
+		[one] => { (name(one), one.to_string_lossy().into_owned()) }
+		_ => {
+			// Items from search results can come from different folders
+			let parent = paths.first().and_then(|p| p.parent());
+			let same = paths.iter().all(|p| p.parent() == parent);
+			let where_ = if same { parent.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default() } else { "Several folders".to_string() };
+			(format!("{} items", paths.len()), where_)
+		}
+	};
+	
+	let kinds: HashSet<String> = paths.iter().map(|p| mime::forPath(p)).collect();
+	let kind = match kinds.len() {
+		1 => { kinds.into_iter().next().unwrap() }
+		_ => { "Several types".to_string() }
+	};
+	
+	let first = metas.first().and_then(|m| m.as_ref());
+	let single = if paths.len() == 1 { first } else { None };
+	let time = |t: Option<SystemTime>| t.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+	                                    .map(|d| formatTime(localTime(d.as_secs() as i64), "%Y-%m-%d %H:%M:%S"))
+	                                    .unwrap_or_default();
+	let linkTarget = match paths {
+		[one] => { fs::read_link(one).map(|t| t.to_string_lossy().into_owned()).unwrap_or_default() }
+		_ => { String::new() }
+	};
+	// Owners and modes are shown when all items share them
+	let common = |f: &dyn Fn(&fs::Metadata) -> String| {
+		let values: HashSet<String> = metas.iter().flatten().map(f).collect();
+		if values.len() == 1 { values.into_iter().next().unwrap() } else { "Several".to_string() }
+	};
+	
+	Summary {
+		title,
+		path,
+		kind,
+		linkTarget,
+		modified: time(single.and_then(|m| m.modified().ok())),
+		accessed: time(single.and_then(|m| m.accessed().ok())),
+		created: time(single.and_then(|m| m.created().ok())),
+		owner: common(&|m| ownerName(m)),
+		group: common(&|m| groupName(m)),
+		mode: first.and_then(mode),
+		hasDirs: direct.dirs > 0,
+		direct,
+	}
+}
+
+/// Walks everything below the paths in the background, without following symlinks. Stops when the receiver is dropped.
+pub fn countSizes(paths: Vec<PathBuf>) -> Receiver<SizeEvent> {
+	let (send, receiver) = channel();
+	thread::Builder::new().name("Size count".into()).spawn(move || {
+		let mut counter = Counter { totals: Totals::default(), seen: HashSet::new(), send, lastSent: Instant::now() };
+		for path in &paths {
+			if counter.walk(path, true).is_err() {
+				return;
+			}
+		}
+		let _ = counter.send.send(SizeEvent::Done(counter.totals));
+	}).map_err(|err| println!("Failed to start size count: {}", err)).ok();
+	receiver
+}
+
+struct Counter {
+	totals: Totals,
+	/// Files with several hard links are only counted once
+	seen: HashSet<(u64, u64)>,
+	send: Sender<SizeEvent>,
+	lastSent: Instant,
+}
+
+impl Counter {
+	/// Err once nobody listens anymore
+	fn walk(&mut self, path: &Path, selected: bool) -> Result<(), ()> {
+		let meta = match fs::symlink_metadata(path) {
+			Ok(m) => { m }
+			Err(_) => { return Ok(()); }
+		};
+		if meta.is_dir() {
+			if !selected {
+				self.totals.dirs += 1;
+			}
+			self.totals.onDisk += onDisk(&meta);
+			match fs::read_dir(path) {
+				Ok(read) => {
+					for entry in read.flatten() {
+						self.walk(&entry.path(), false)?;
+					}
+				}
+				Err(_) => { self.totals.unreadable += 1; }
+			}
+		} else {
+			self.totals.files += 1;
+			if self.isFirstLink(&meta) {
+				self.totals.bytes += meta.len();
+				self.totals.onDisk += onDisk(&meta);
+			}
+		}
+		
+		if self.lastSent.elapsed() > PROGRESS_INTERVAL {
+			self.lastSent = Instant::now();
+			self.send.send(SizeEvent::Progress(self.totals)).map_err(|_| ())?;
+		}
+		Ok(())
+	}
+	
+	#[cfg(unix)]
+	fn isFirstLink(&mut self, meta: &fs::Metadata) -> bool {
+		use std::os::unix::fs::MetadataExt;
+		meta.nlink() < 2 || self.seen.insert((meta.dev(), meta.ino()))
+	}
+	
+	#[cfg(not(unix))]
+	fn isFirstLink(&mut self, _meta: &fs::Metadata) -> bool {
+		true
+	}
+}
+
+#[cfg(unix)]
+fn onDisk(meta: &fs::Metadata) -> u64 {
+	use std::os::unix::fs::MetadataExt;
+	meta.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn onDisk(meta: &fs::Metadata) -> u64 {
+	meta.len()
+}
+
+/// "12.3 MB (12,345,678 bytes)"
+pub fn describeSize(bytes: u64) -> String {
+	let digits = bytes.to_string();
+	let mut grouped = String::new();
+	for (i, c) in digits.chars().enumerate() {
+		if i > 0 && (digits.len() - i).is_multiple_of(3) {
+			grouped.push(',');
+		}
+		grouped.push(c);
+	}
+	format!("{} ({} bytes)", crate::places::humanSize(bytes), grouped)
+}
+
+/// "3 files, 1 folder"
+pub fn describeCounts(files: u64, dirs: u64) -> String {
+	let plural = |n: u64, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+	format!("{}, {}", plural(files, "file", "files"), plural(dirs, "folder", "folders"))
+}
+
+/// The changes to apply, None leaves that part as it is
+pub struct Change {
+	pub mode: Option<u32>,
+	pub owner: Option<String>,
+	pub group: Option<String>,
+	/// Also changes everything inside of selected folders
+	pub recursive: bool,
+}
+
+#[cfg(unix)]
+fn mode(meta: &fs::Metadata) -> Option<u32> {
+	use std::os::unix::fs::PermissionsExt;
+	Some(meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode(_meta: &fs::Metadata) -> Option<u32> {
+	None
+}
+
+#[cfg(unix)]
+fn ownerName(meta: &fs::Metadata) -> String {
+	use std::os::unix::fs::MetadataExt;
+	users::userName(meta.uid())
+}
+
+#[cfg(unix)]
+fn groupName(meta: &fs::Metadata) -> String {
+	use std::os::unix::fs::MetadataExt;
+	users::groupName(meta.gid())
+}
+
+#[cfg(not(unix))]
+fn ownerName(_meta: &fs::Metadata) -> String {
+	String::new()
+}
+
+#[cfg(not(unix))]
+fn groupName(_meta: &fs::Metadata) -> String {
+	String::new()
+}
+
+/// How many items were changed. Stops at the first failure, since it usually fails the same way for the rest.
+/// Inside of folders, files keep their execute bits so a recursive change does not make every file executable.
+#[cfg(unix)]
+pub fn apply(paths: &[PathBuf], change: &Change) -> Result<usize, String> {
+	let uid = match &change.owner {
+		None => { None }
+		Some(name) => { Some(users::userId(name).ok_or_else(|| format!("There is no user {}", name))?) }
+	};
+	let gid = match &change.group {
+		None => { None }
+		Some(name) => { Some(users::groupId(name).ok_or_else(|| format!("There is no group {}", name))?) }
+	};
+	
+	let mut changed = 0;
+	for path in paths {
+		applyTo(path, change, uid, gid, true, &mut changed)?;
+	}
+	Ok(changed)
+}
+
+#[cfg(unix)]
+fn applyTo(path: &Path, change: &Change, uid: Option<u32>, gid: Option<u32>, selected: bool, changed: &mut usize) -> Result<(), String> {
+	use std::os::unix::fs::PermissionsExt;
+	let meta = fs::symlink_metadata(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+	
+	if uid.is_some() || gid.is_some() {
+		std::os::unix::fs::lchown(path, uid, gid).map_err(|err| format!("Failed to change the owner of {}: {}", path.display(), err))?;
+	}
+	// Symlinks have no permissions of their own
+	if let (Some(mode), false) = (change.mode, meta.file_type().is_symlink()) {
+		let old = meta.permissions().mode() & 0o7777;
+		let new = if selected || meta.is_dir() { mode } else { (mode & !0o111) | (old & 0o111) };
+		if new != old {
+			fs::set_permissions(path, fs::Permissions::from_mode(new)).map_err(|err| format!("Failed to change the permissions of {}: {}", path.display(), err))?;
+		}
+	}
+	*changed += 1;
+	
+	if change.recursive && meta.is_dir() {
+		let read = fs::read_dir(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+		for entry in read.flatten() {
+			applyTo(&entry.path(), change, uid, gid, false, changed)?;
+		}
+	}
+	Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn apply(_paths: &[PathBuf], _change: &Change) -> Result<usize, String> {
+	Err("Permissions can not be changed on this platform".to_string())
+}
+
+/// Names of users and groups from the system databases
+#[cfg(unix)]
+mod users {
+	use std::ffi::{CStr, CString};
+	
+	const BUFFER: usize = 16 * 1024;
+	
+	/// The number if the user has no name
+	pub fn userName(uid: u32) -> String {
+		let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+		let mut buf = vec![0 as libc::c_char; BUFFER];
+		let mut found = std::ptr::null_mut();
+		let res = unsafe { libc::getpwuid_r(uid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut found) };
+		if res != 0 || found.is_null() {
+			return uid.to_string();
+		}
+		unsafe { CStr::from_ptr(entry.pw_name) }.to_string_lossy().into_owned()
+	}
+	
+	pub fn groupName(gid: u32) -> String {
+		let mut entry: libc::group = unsafe { std::mem::zeroed() };
+		let mut buf = vec![0 as libc::c_char; BUFFER];
+		let mut found = std::ptr::null_mut();
+		let res = unsafe { libc::getgrgid_r(gid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut found) };
+		if res != 0 || found.is_null() {
+			return gid.to_string();
+		}
+		unsafe { CStr::from_ptr(entry.gr_name) }.to_string_lossy().into_owned()
+	}
+	
+	/// Numbers are taken as they are
+	pub fn userId(name: &str) -> Option<u32> {
+		if let Ok(id) = name.parse() {
+			return Some(id);
+		}
+		let cName = CString::new(name).ok()?;
+		let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+		let mut buf = vec![0 as libc::c_char; BUFFER];
+		let mut found = std::ptr::null_mut();
+		let res = unsafe { libc::getpwnam_r(cName.as_ptr(), &mut entry, buf.as_mut_ptr(), buf.len(), &mut found) };
+		if res != 0 || found.is_null() { None } else { Some(entry.pw_uid) }
+	}
+	
+	pub fn groupId(name: &str) -> Option<u32> {
+		if let Ok(id) = name.parse() {
+			return Some(id);
+		}
+		let cName = CString::new(name).ok()?;
+		let mut entry: libc::group = unsafe { std::mem::zeroed() };
+		let mut buf = vec![0 as libc::c_char; BUFFER];
+		let mut found = std::ptr::null_mut();
+		let res = unsafe { libc::getgrnam_r(cName.as_ptr(), &mut entry, buf.as_mut_ptr(), buf.len(), &mut found) };
+		if res != 0 || found.is_null() { None } else { Some(entry.gr_gid) }
+	}
+}
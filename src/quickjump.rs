@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use crate::{bookmarks, frecency};
+use crate::matching::fuzzyScore;
+
+const MAX_RESULTS: usize = 20;
+
+pub struct Candidate {
+	pub path: String,
+	pub label: String,
+	/// Where the candidate comes from, shown next to it
+	pub source: &'static str,
+	score: f64,
+}
+
+/// Bookmarks and visited directories that fuzzy match text, best first. Matches in the last path component beat matches spread over the whole path.
+pub fn query(text: &str) -> Vec<Candidate> {
+	let pattern = text.trim().to_lowercase();
+	let mut res: Vec<Candidate> = vec![];
+	
+	for b in bookmarks::read() {
+		let path = b.path.to_string_lossy().into_owned();
+		// Bookmarks are picked on purpose, so they start out ahead of most visits
+		if let Some(score) = matchScore(&pattern, &path, &b.label, 50.0) {
+			res.push(Candidate { path, label: b.label, source: "Bookmark", score });
+		}
+	}
+	
+	for (path, frecency) in frecency::scored() {
+		if res.iter().any(|c| c.path == path) {
+			continue;
+		}
+		let label = Path::new(&path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path.clone());
+		if let Some(score) = matchScore(&pattern, &path, &label, frecency) {
+			res.push(Candidate { path, label, source: "Recent", score });
+		}
+	}
+	
+	res.sort_by(|a, b| b.score.total_cmp(&a.score));
+	res.truncate(MAX_RESULTS);
+	res
+}
+
+fn matchScore(pattern: &str, path: &str, label: &str, frecency: f64) -> Option<f64> {
+	let weight = 10.0 * (1.0 + frecency).ln();
+	if pattern.is_empty() {
+		return Some(weight);
+	}
+	let fuzzy = match (fuzzyScore(pattern, label), fuzzyScore(pattern, path)) {
+		(Some(l), _) => { l * 2 }
+		(None, Some(p)) => { p }
+		(None, None) => { return None; }
+	};
+	Some(fuzzy as f64 + weight)
+}
@@ -2,21 +2,28 @@ use std::{fmt, thread};
 use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::io::{BufRead, Cursor, Read, Seek};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
 use std::ops::Deref;
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 use std::sync::mpsc::channel;
 use std::thread::scope;
 use std::time::Duration;
 
-use image::{ColorType, DynamicImage, Pixel, Rgba};
+use image::{AnimationDecoder, ColorType, DynamicImage, ImageFormat, Pixel, Rgba};
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
 use image::imageops::FilterType;
 use image::io::Reader as ImageReader;
 use rust_embed::RustEmbed;
 use slint::{Image, Rgba8Pixel, SharedPixelBuffer};
 use zip::ZipArchive;
 use crate::icon::GlobalIcons;
+use crate::photo;
 
 #[derive(RustEmbed)]
 #[folder = "src/ui/"]
@@ -27,7 +34,8 @@ struct BuiltInAssets;
 
 pub struct ImageSequence {
 	frames: Vec<Image>,
-	timePerFrame: Duration,
+	/// When each frame ends, counted from the start of the sequence
+	ends: Vec<Duration>,
 }
 
 fn readFile(namePrefix: &str, extension: &str, zip: &mut ZipArchive<Cursor<&[u8]>>, i: usize) -> Result<(i32, RgbImg), String> {
@@ -79,7 +87,7 @@ impl ImageSequence {
 		scope(|scope| {
 			let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
 			let usedCores = min(cores, count);
-			let chunkSize = ceilDiv(count, usedCores);
+			let chunkSize = count.div_ceil(usedCores);
 			
 			let mut tasks = vec![];
 			
@@ -143,20 +151,45 @@ impl ImageSequence {
 				return Err(format!("Missing frame {}", frames.len()));
 			}
 			
+			let timePerFrame = Duration::from_secs_f32(1.0 / framerate);
 			Ok(ImageSequence {
+				ends: (1..=frames.len() as u32).map(|i| timePerFrame * i).collect(),
 				frames,
-				timePerFrame: Duration::from_secs_f32(1.0 / framerate),
 			})
 		})
 	}
 	
-	pub fn timePerFrame(&self) -> Duration { self.timePerFrame }
+	/// The frames of an animated image file, as RgbImg::readFrames decoded them. Must be called on the UI thread.
+	pub fn fromFrames(frames: Vec<(RgbImg, Duration)>) -> Self {
+		let mut end = Duration::ZERO;
+		let ends = frames.iter().map(|(_, delay)| {
+			end += *delay;
+			end
+		}).collect();
+		ImageSequence { frames: frames.iter().map(|(img, _)| img.asImage()).collect(), ends }
+	}
+	
+	pub fn frameCount(&self) -> usize { self.frames.len() }
+	
+	/// How long the shortest frame is shown, often enough to check for the next one
+	pub fn timePerFrame(&self) -> Duration {
+		let mut last = Duration::ZERO;
+		self.ends.iter().map(|end| {
+			let d = *end - last;
+			last = *end;
+			d
+		}).min().unwrap_or_default()
+	}
 	
 	pub fn getFrame(&self, timeSinceStart: Duration) -> &Image {
-		let index = timeSinceStart.as_micros() / self.timePerFrame.as_micros();
-		let wrapped = (index % self.frames.len() as u128) as usize;
+		let total = self.ends.last().copied().unwrap_or_default();
+		if total.is_zero() {
+			return self.frames.first().unwrap();
+		}
+		let wrapped = Duration::from_nanos((timeSinceStart.as_nanos() % total.as_nanos()) as u64);
+		let index = self.ends.partition_point(|end| *end <= wrapped);
 		
-		self.frames.get(wrapped).unwrap()
+		self.frames.get(index).unwrap()
 	}
 }
 
@@ -165,6 +198,8 @@ pub struct RgbImg {
 	width: u32,
 	height: u32,
 	isDefault: bool,
+	/// The first frame of an animation, see hasAnimation
+	animated: bool,
 }
 
 impl Display for RgbImg {
@@ -184,6 +219,29 @@ impl Display for RgbImg {
 
 impl RgbImg {
 	pub fn isDefault(&self) -> bool { self.isDefault }
+	pub fn isAnimated(&self) -> bool { self.animated }
+	pub fn width(&self) -> u32 { self.width }
+	pub fn height(&self) -> u32 { self.height }
+	
+	/// Turned clockwise by the number of quarter turns
+	pub fn rotated(&self, quarterTurns: u32) -> RgbImg {
+		let (w, h) = (self.width as usize, self.height as usize);
+		let turns = quarterTurns % 4;
+		let (nw, nh) = if turns % 2 == 1 { (h, w) } else { (w, h) };
+		let mut pixels = self.pixels.clone();
+		for y in 0..h {
+			for x in 0..w {
+				let (nx, ny) = match turns {
+					1 => { (h - 1 - y, x) }
+					2 => { (w - 1 - x, h - 1 - y) }
+					3 => { (y, w - 1 - x) }
+					_ => { (x, y) }
+				};
+				pixels[nx + ny * nw] = self.pixels[x + y * w];
+			}
+		}
+		RgbImg { pixels, width: nw as u32, height: nh as u32, isDefault: self.isDefault, animated: self.animated }
+	}
 	
 	pub fn asImageCached(&self,globalIcon: &RwLock<GlobalIcons>, defaultCache: &mut Option<Image>) -> Image {
 		if self.isDefault() {
@@ -211,6 +269,13 @@ impl RgbImg {
 	pub fn read(path: &str) -> Result<RgbImg, String> {
 		RgbImg::readSizeLimited(path, Some(256))
 	}
+	
+	/// Like read, remembering whether the file has more frames
+	pub fn readThumbnail(path: &str) -> Result<RgbImg, String> {
+		let mut img = RgbImg::read(path)?;
+		img.animated = hasAnimation(path);
+		Ok(img)
+	}
 	pub fn readSizeLimited(path: &str, maxSize: Option<u32>) -> Result<RgbImg, String> {
 		let mut img = None;
 		
@@ -222,32 +287,135 @@ impl RgbImg {
 			}
 		}
 		
-		let mut img = match img {
-			None => {
-				ImageReader::open(path)
-					.and_then(|i| i.with_guessed_format())
-					.map_err(|err| format!("{}", err))
-					.and_then(|i| i.decode().map_err(|err| format!("{}", err)))?
-			}
+		let img = match img {
+			None => { decodeFile(path, maxSize)? }
 			Some(i) => { i }
 		};
 		
-		if let Some(maxSize) = maxSize {
-			let s = max(img.width(), img.height());
-			if s > maxSize {
-				let fac = maxSize as f32 / s as f32;
-				img = img.resize(
-					(img.width() as f32 * fac) as u32,
-					(img.height() as f32 * fac) as u32,
-					FilterType::Triangle,
-				);
+		Ok(convert(limitSize(img, maxSize), path))
+	}
+	
+	/// The frames of an animated GIF, PNG or WebP with how long each is shown, or the only frame of any other image.
+	/// Decoding stops after maxFrames or once the frames take up maxBytes, the frames decoded until then are kept.
+	pub fn readFrames(path: &str, maxSize: Option<u32>, maxFrames: usize, maxBytes: usize) -> Result<Vec<(RgbImg, Duration)>, String> {
+		let open = || File::open(path).map(BufReader::new).map_err(|err| format!("{}", err));
+		let frames = match ImageFormat::from_path(path) {
+			Ok(ImageFormat::Gif) => {
+				Some(GifDecoder::new(open()?).map_err(|err| format!("{}", err))?.into_frames())
 			}
-		}
+			Ok(ImageFormat::Png) => {
+				let decoder = PngDecoder::new(open()?).map_err(|err| format!("{}", err))?;
+				if decoder.is_apng() { Some(decoder.apng().into_frames()) } else { None }
+			}
+			Ok(ImageFormat::WebP) => {
+				let decoder = WebPDecoder::new(open()?).map_err(|err| format!("{}", err))?;
+				if decoder.has_animation() { Some(decoder.into_frames()) } else { None }
+			}
+			_ => { None }
+		};
+		let frames = match frames {
+			None => { return Ok(vec![(RgbImg::readSizeLimited(path, maxSize)?, Duration::ZERO)]); }
+			Some(f) => { f }
+		};
 		
-		Ok(convert(img, path))
+		let mut res = vec![];
+		let mut bytes = 0;
+		for frame in frames.take(maxFrames) {
+			let frame = match frame {
+				Ok(f) => { f }
+				// A broken frame ends the animation early
+				Err(_) if !res.is_empty() => { break; }
+				Err(err) => { return Err(format!("{}", err)); }
+			};
+			let (num, denom) = frame.delay().numer_denom_ms();
+			let mut delay = Duration::from_micros(num as u64 * 1000 / denom.max(1) as u64);
+			// Like browsers, very short delays are taken as "as fast as possible" from old encoders
+			if delay < Duration::from_millis(20) {
+				delay = Duration::from_millis(100);
+			}
+			let img = convert(limitSize(DynamicImage::ImageRgba8(frame.into_buffer()), maxSize), "");
+			bytes += img.pixels.len() * 4;
+			res.push((img, delay));
+			if bytes >= maxBytes {
+				break;
+			}
+		}
+		if res.is_empty() {
+			return Err("No frames".to_string());
+		}
+		Ok(res)
 	}
 }
 
+/// Decodes the image upright as its EXIF data says. When only a small image is needed, photos are read from the thumbnail
+/// embedded in their EXIF data if it is big enough, and other JPEGs are decoded at a reduced scale.
+fn decodeFile(path: &str, maxSize: Option<u32>) -> Result<DynamicImage, String> {
+	let format = ImageFormat::from_path(path).ok();
+	let info = match format {
+		Some(ImageFormat::Jpeg | ImageFormat::Tiff | ImageFormat::Png | ImageFormat::WebP) => { photo::read(Path::new(path)) }
+		_ => { None }
+	};
+	
+	let embedded = match (maxSize, info.as_ref().and_then(|i| i.thumbnail.as_ref())) {
+		(Some(maxSize), Some(thumbnail)) => { imgFrom(Cursor::new(thumbnail.as_slice())).ok().filter(|t| max(t.width(), t.height()) >= maxSize) }
+		_ => { None }
+	};
+	let scaled = match maxSize {
+		Some(maxSize) if embedded.is_none() && format == Some(ImageFormat::Jpeg) => {
+			let size = maxSize.min(u16::MAX as u32) as u16;
+			// Falls back to the full decode below for files that only claim to be JPEGs
+			File::open(path).ok()
+			                .and_then(|f| JpegDecoder::new(BufReader::new(f)).ok())
+			                .and_then(|mut d| d.scale(size, size).ok().map(|_| d))
+			                .and_then(|d| DynamicImage::from_decoder(d).ok())
+		}
+		_ => { None }
+	};
+	
+	let img = match embedded.or(scaled) {
+		Some(i) => { i }
+		None => {
+			ImageReader::open(path)
+				.and_then(|i| i.with_guessed_format())
+				.map_err(|err| format!("{}", err))
+				.and_then(|i| i.decode().map_err(|err| format!("{}", err)))?
+		}
+	};
+	Ok(match info {
+		Some(i) => { orient(img, i.orientation) }
+		None => { img }
+	})
+}
+
+/// Turns the image by an EXIF orientation, 1 to 8
+fn orient(img: DynamicImage, orientation: u32) -> DynamicImage {
+	match orientation {
+		2 => { img.fliph() }
+		3 => { img.rotate180() }
+		4 => { img.flipv() }
+		5 => { img.rotate90().fliph() }
+		6 => { img.rotate90() }
+		7 => { img.rotate270().fliph() }
+		8 => { img.rotate270() }
+		_ => { img }
+	}
+}
+
+fn limitSize(img: DynamicImage, maxSize: Option<u32>) -> DynamicImage {
+	if let Some(maxSize) = maxSize {
+		let s = max(img.width(), img.height());
+		if s > maxSize {
+			let fac = maxSize as f32 / s as f32;
+			return img.resize(
+				(img.width() as f32 * fac) as u32,
+				(img.height() as f32 * fac) as u32,
+				FilterType::Triangle,
+			);
+		}
+	}
+	img
+}
+
 fn convert(img: DynamicImage, path: &str) -> RgbImg {
 	let w = img.width();
 	let h = img.height();
@@ -342,11 +510,22 @@ fn convert(img: DynamicImage, path: &str) -> RgbImg {
 		width: w,
 		height: h,
 		isDefault: path.eq(">>default.png"),
+		animated: false,
 	}
 }
 
-fn ceilDiv(a: usize, b: usize) -> usize {
-	(a + (b - 1)) / b
+/// Whether the GIF, PNG or WebP file has more than one frame. Only PNG and WebP say so in their header, GIFs are decoded up to their second frame.
+pub fn hasAnimation(path: &str) -> bool {
+	let file = match File::open(path) {
+		Ok(f) => { BufReader::new(f) }
+		Err(_) => { return false; }
+	};
+	match ImageFormat::from_path(path) {
+		Ok(ImageFormat::Gif) => { GifDecoder::new(file).map(|d| d.into_frames().take(2).count() == 2).unwrap_or(false) }
+		Ok(ImageFormat::Png) => { PngDecoder::new(file).map(|d| d.is_apng()).unwrap_or(false) }
+		Ok(ImageFormat::WebP) => { WebPDecoder::new(file).map(|d| d.has_animation()).unwrap_or(false) }
+		_ => { false }
+	}
 }
 
 fn imgFrom<R: BufRead + Seek>(data: R) -> Result<DynamicImage, String> {
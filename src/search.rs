@@ -0,0 +1,390 @@
+use std::{fs, thread};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ignore::{WalkBuilder, WalkState};
+
+use crate::config::{IgnoreMode, Settings};
+use crate::grep::ContentMatcher;
+use crate::index;
+use crate::matching::{fuzzyScore, MatchMode, NameMatcher};
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Kind {
+	Any,
+	File,
+	Folder,
+	Image,
+	Video,
+	Audio,
+	Document,
+	Archive,
+}
+
+impl Kind {
+	/// Same order as the type selector in the UI
+	pub fn fromIndex(index: i32) -> Self {
+		match index {
+			1 => { Kind::File }
+			2 => { Kind::Folder }
+			3 => { Kind::Image }
+			4 => { Kind::Video }
+			5 => { Kind::Audio }
+			6 => { Kind::Document }
+			7 => { Kind::Archive }
+			_ => { Kind::Any }
+		}
+	}
+	
+	fn extensions(&self) -> &'static [&'static str] {
+		match self {
+			Kind::Image => { &["png", "jpg", "jpeg", "gif", "bmp", "ico", "tiff", "tif", "webp", "avif", "svg", "heic", "pnm", "dds", "tga"] }
+			Kind::Video => { &["mp4", "mkv", "webm", "avi", "mov", "wmv", "flv", "m4v", "mpg", "mpeg"] }
+			Kind::Audio => { &["mp3", "flac", "ogg", "opus", "wav", "m4a", "aac", "wma"] }
+			Kind::Document => { &["pdf", "txt", "md", "doc", "docx", "odt", "rtf", "xls", "xlsx", "ods", "ppt", "pptx", "odp", "epub"] }
+			Kind::Archive => { &["zip", "tar", "gz", "tgz", "xz", "bz2", "zst", "7z", "rar", "jar"] }
+			_ => { &[] }
+		}
+	}
+	
+	fn matches(&self, path: &Path, isDir: bool) -> bool {
+		match self {
+			Kind::Any => { true }
+			Kind::File => { !isDir }
+			Kind::Folder => { isDir }
+			_ => {
+				!isDir && path.extension()
+				              .and_then(|e| e.to_str())
+				              .map(|e| self.extensions().contains(&e.to_lowercase().as_str()))
+				              .unwrap_or(false)
+			}
+		}
+	}
+}
+
+pub struct SearchQuery {
+	pub root: PathBuf,
+	pub name: NameMatcher,
+	pub minSize: Option<u64>,
+	pub maxSize: Option<u64>,
+	pub after: Option<SystemTime>,
+	pub before: Option<SystemTime>,
+	pub kind: Kind,
+	/// Only files containing this are found
+	pub content: Option<ContentMatcher>,
+	pub showHidden: bool,
+	pub respectIgnore: bool,
+}
+
+/// The raw text of the search fields
+pub struct SearchFields<'a> {
+	pub root: &'a str,
+	pub text: &'a str,
+	pub mode: MatchMode,
+	pub minSize: &'a str,
+	pub maxSize: &'a str,
+	pub after: &'a str,
+	pub before: &'a str,
+	pub kind: Kind,
+	pub content: &'a str,
+	pub contentRegex: bool,
+}
+
+impl SearchQuery {
+	/// Builds a query from the search fields, empty fields don't restrict anything
+	pub fn parse(fields: &SearchFields, settings: &Settings) -> Result<Self, String> {
+		let content = if fields.content.is_empty() { None } else { Some(ContentMatcher::new(fields.content, fields.contentRegex)?) };
+		Ok(SearchQuery {
+			root: PathBuf::from(fields.root),
+			name: NameMatcher::new(fields.text, fields.mode)?,
+			minSize: parseSize(fields.minSize)?,
+			maxSize: parseSize(fields.maxSize)?,
+			after: parseDate(fields.after)?,
+			// The whole "before" day is included
+			before: parseDate(fields.before)?.map(|d| d + Duration::from_secs(24 * 60 * 60)),
+			kind: fields.kind,
+			// Like grep tools, contents of ignored files are never searched
+			respectIgnore: settings.ignoreMode == IgnoreMode::Hide || content.is_some(),
+			content,
+			showHidden: settings.showHidden,
+		})
+	}
+	
+	fn needsMetadata(&self) -> bool {
+		self.minSize.is_some() || self.maxSize.is_some() || self.after.is_some() || self.before.is_some()
+	}
+	
+	fn matches(&self, path: &Path, isDir: bool) -> bool {
+		let name = match path.file_name().and_then(|n| n.to_str()) {
+			None => { return false; }
+			Some(n) => { n }
+		};
+		if !self.matchesName(name, isDir) {
+			return false;
+		}
+		if !self.needsMetadata() {
+			return true;
+		}
+		
+		let meta = match fs::metadata(path) {
+			Ok(m) => { m }
+			Err(_) => { return false; }
+		};
+		match meta.modified() {
+			Ok(modified) => { self.matchesMetadata(isDir, meta.len(), modified) }
+			Err(_) => { false }
+		}
+	}
+	
+	fn matchesName(&self, name: &str, isDir: bool) -> bool {
+		if isDir && self.content.is_some() {
+			return false;
+		}
+		self.kind.matches(Path::new(name), isDir) && self.name.isMatch(name)
+	}
+	
+	fn matchesMetadata(&self, isDir: bool, size: u64, modified: SystemTime) -> bool {
+		if self.minSize.map(|m| isDir || size < m).unwrap_or(false) { return false; }
+		if self.maxSize.map(|m| isDir || size > m).unwrap_or(false) { return false; }
+		if self.after.map(|a| modified < a).unwrap_or(false) { return false; }
+		if self.before.map(|b| modified >= b).unwrap_or(false) { return false; }
+		true
+	}
+}
+
+pub enum SearchEvent {
+	/// Content searches also send the matching lines
+	Found(PathBuf, Vec<String>),
+	Done,
+}
+
+/// A running search. Dropping it cancels the search.
+pub struct SearchHandle {
+	pub description: String,
+	pub receiver: Receiver<SearchEvent>,
+	cancelled: Arc<AtomicBool>,
+	scanned: Arc<AtomicU64>,
+	pub found: usize,
+	pub done: bool,
+}
+
+impl SearchHandle {
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+	
+	pub fn isCancelled(&self) -> bool { self.cancelled.load(Ordering::Relaxed) }
+	
+	pub fn progress(&self) -> String {
+		let scanned = self.scanned.load(Ordering::Relaxed);
+		if !self.done {
+			format!("Searching... {} found, {} scanned", self.found, scanned)
+		} else if self.isCancelled() {
+			format!("Search cancelled, {} found in {} scanned", self.found, scanned)
+		} else {
+			format!("Search done, {} found in {} scanned", self.found, scanned)
+		}
+	}
+}
+
+impl Drop for SearchHandle {
+	fn drop(&mut self) {
+		self.cancel();
+	}
+}
+
+pub fn start(query: SearchQuery, description: String) -> SearchHandle {
+	let (send, receiver) = channel();
+	let cancelled = Arc::new(AtomicBool::new(false));
+	let scanned = Arc::new(AtomicU64::new(0));
+	
+	let handle = SearchHandle {
+		description,
+		receiver,
+		cancelled: cancelled.clone(),
+		scanned: scanned.clone(),
+		found: 0,
+		done: false,
+	};
+	
+	thread::spawn(move || {
+		if let NameMatcher::Fuzzy(pattern) = &query.name {
+			let pattern = pattern.clone();
+			searchRanked(query, &pattern, &send, cancelled, scanned);
+		} else if !searchIndex(&query, &send, &scanned) {
+			walk(query, send.clone(), cancelled, scanned);
+		}
+		let _ = send.send(SearchEvent::Done);
+	});
+	
+	handle
+}
+
+/// Fuzzy results can only be ranked once they are all known, so they are collected and sent best first
+fn searchRanked(query: SearchQuery, pattern: &str, send: &Sender<SearchEvent>, cancelled: Arc<AtomicBool>, scanned: Arc<AtomicU64>) {
+	let (allSend, allReceiver) = channel();
+	if !searchIndex(&query, &allSend, &scanned) {
+		walk(query, allSend, cancelled, scanned);
+	}
+	
+	let mut found: Vec<(i64, PathBuf, Vec<String>)> = allReceiver.try_iter().filter_map(|event| {
+		match event {
+			SearchEvent::Found(path, snippets) => {
+				let score = path.file_name().and_then(|n| n.to_str()).and_then(|n| fuzzyScore(pattern, n)).unwrap_or(i64::MIN);
+				Some((score, path, snippets))
+			}
+			SearchEvent::Done => { None }
+		}
+	}).collect();
+	found.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+	
+	for (_, path, snippets) in found {
+		if send.send(SearchEvent::Found(path, snippets)).is_err() {
+			break;
+		}
+	}
+}
+
+/// Answers the query from the file name index if it covers the root. Ignore files and file contents can only be checked by walking the disk.
+fn searchIndex(query: &SearchQuery, send: &Sender<SearchEvent>, scanned: &AtomicU64) -> bool {
+	if query.respectIgnore || query.content.is_some() {
+		return false;
+	}
+	let res = index::query(&query.root, |e| {
+		query.matchesName(e.name, e.isDir) &&
+			(!query.needsMetadata() || query.matchesMetadata(e.isDir, e.size, UNIX_EPOCH + Duration::from_secs(e.modified.max(0) as u64)))
+	});
+	let (paths, count) = match res {
+		None => { return false; }
+		Some(r) => { r }
+	};
+	scanned.store(count as u64, Ordering::Relaxed);
+	
+	for path in paths {
+		let hidden = path.strip_prefix(&query.root).map(|rel| {
+			rel.iter().any(|n| n.to_str().map(|n| n.starts_with('.')).unwrap_or(false))
+		}).unwrap_or(false);
+		if hidden && !query.showHidden {
+			continue;
+		}
+		if send.send(SearchEvent::Found(path, vec![])).is_err() {
+			break;
+		}
+	}
+	true
+}
+
+fn walk(query: SearchQuery, send: Sender<SearchEvent>, cancelled: Arc<AtomicBool>, scanned: Arc<AtomicU64>) {
+	let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+	let query = Arc::new(query);
+	
+	WalkBuilder::new(&query.root)
+		.hidden(!query.showHidden)
+		.ignore(query.respectIgnore)
+		.git_ignore(query.respectIgnore)
+		.git_exclude(query.respectIgnore)
+		.git_global(false)
+		.parents(query.respectIgnore)
+		.threads(threads)
+		.build_parallel()
+		.run(|| {
+			let send = send.clone();
+			let query = query.clone();
+			let cancelled = cancelled.clone();
+			let scanned = scanned.clone();
+			Box::new(move |entry| {
+				if cancelled.load(Ordering::Relaxed) {
+					return WalkState::Quit;
+				}
+				let entry = match entry {
+					Ok(e) => { e }
+					Err(_) => { return WalkState::Continue; }
+				};
+				// The root itself is not a result
+				if entry.depth() == 0 {
+					return WalkState::Continue;
+				}
+				scanned.fetch_add(1, Ordering::Relaxed);
+				
+				let isDir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+				if !query.matches(entry.path(), isDir) {
+					return WalkState::Continue;
+				}
+				
+				let snippets = match &query.content {
+					None => { vec![] }
+					Some(content) => {
+						let snippets = content.searchFile(entry.path());
+						if snippets.is_empty() {
+							return WalkState::Continue;
+						}
+						snippets
+					}
+				};
+				if send.send(SearchEvent::Found(entry.into_path(), snippets)).is_err() {
+					return WalkState::Quit;
+				}
+				WalkState::Continue
+			})
+		});
+}
+
+/// Accepts plain byte counts and k/M/G/T suffixes (powers of 1024)
+pub fn parseSize(text: &str) -> Result<Option<u64>, String> {
+	let text = text.trim();
+	if text.is_empty() {
+		return Ok(None);
+	}
+	let lower = text.to_lowercase();
+	let num = lower.trim_end_matches(|c: char| c.is_alphabetic());
+	let unit = &lower[num.len()..];
+	let mul: u64 = match unit {
+		"" | "b" => { 1 }
+		"k" | "kb" | "kib" => { 1 << 10 }
+		"m" | "mb" | "mib" => { 1 << 20 }
+		"g" | "gb" | "gib" => { 1 << 30 }
+		"t" | "tb" | "tib" => { 1 << 40 }
+		_ => { return Err(format!("Unknown size unit \"{}\"", unit)); }
+	};
+	let num: f64 = num.trim().parse().map_err(|_| format!("Invalid size \"{}\"", text))?;
+	Ok(Some((num * mul as f64) as u64))
+}
+
+/// Accepts YYYY-MM-DD, interpreted as midnight UTC
+pub fn parseDate(text: &str) -> Result<Option<SystemTime>, String> {
+	let text = text.trim();
+	if text.is_empty() {
+		return Ok(None);
+	}
+	let err = || format!("Invalid date \"{}\", expected YYYY-MM-DD", text);
+	
+	let parts: Vec<&str> = text.split('-').collect();
+	if parts.len() != 3 {
+		return Err(err());
+	}
+	let y: i64 = parts[0].parse().map_err(|_| err())?;
+	let m: i64 = parts[1].parse().map_err(|_| err())?;
+	let d: i64 = parts[2].parse().map_err(|_| err())?;
+	if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+		return Err(err());
+	}
+	
+	let days = daysFromCivil(y, m, d);
+	if days < 0 {
+		return Ok(Some(UNIX_EPOCH));
+	}
+	Ok(Some(UNIX_EPOCH + Duration::from_secs(days as u64 * 24 * 60 * 60)))
+}
+
+/// Days since 1970-01-01 of a proleptic gregorian date, see http://howardhinnant.github.io/date_algorithms.html
+pub fn daysFromCivil(y: i64, m: i64, d: i64) -> i64 {
+	let y = if m <= 2 { y - 1 } else { y };
+	let era = if y >= 0 { y } else { y - 399 } / 400;
+	let yoe = y - era * 400;
+	let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	era * 146097 + doe - 719468
+}
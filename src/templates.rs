@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::fileops::copyTree;
+use crate::places::userDir;
+
+/// A file "New file" can start from
+pub struct Template {
+	pub label: String,
+	pub path: PathBuf,
+}
+
+/// The files in $XDG_TEMPLATES_DIR followed by the ones listed in the settings
+pub fn list() -> Vec<Template> {
+	let mut paths: Vec<PathBuf> = vec![];
+	let home = home::home_dir();
+	if let Some(dir) = userDir("TEMPLATES").filter(|d| Some(d) != home.as_ref()) {
+		if let Ok(read) = fs::read_dir(dir) {
+			let mut found: Vec<PathBuf> = read.flatten()
+			                                  .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+			                                  .map(|e| e.path())
+			                                  .collect();
+			found.sort_by_key(|p| p.to_string_lossy().to_lowercase());
+			paths.extend(found);
+		}
+	}
+	paths.extend(config::settings().fileTemplates.into_iter().filter(|p| p.exists()));
+	
+	paths.into_iter().map(|path| {
+		let label = path.file_stem().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+		Template { label, path }
+	}).collect()
+}
+
+/// "name.ext", "name 2.ext", ... whichever is free in dir
+fn freeName(dir: &Path, name: &str) -> PathBuf {
+	let (stem, ext) = match name.rfind('.') {
+		Some(i) if i > 0 => { (&name[..i], &name[i..]) }
+		_ => { (name, "") }
+	};
+	let mut n = 1;
+	loop {
+		let candidate = if n == 1 { dir.join(name) } else { dir.join(format!("{} {}{}", stem, n, ext)) };
+		if fs::symlink_metadata(&candidate).is_err() {
+			return candidate;
+		}
+		n += 1;
+	}
+}
+
+pub fn newFolder(dir: &Path) -> Result<PathBuf, String> {
+	let path = freeName(dir, "New folder");
+	fs::create_dir(&path).map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+	Ok(path)
+}
+
+/// An empty file, or a copy of the template named like it
+pub fn newFile(dir: &Path, template: Option<&Path>) -> Result<PathBuf, String> {
+	match template {
+		None => {
+			let path = freeName(dir, "New file");
+			fs::OpenOptions::new().write(true).create_new(true).open(&path).map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+			Ok(path)
+		}
+		Some(template) => {
+			let name = template.file_name().ok_or("Not a template file")?.to_string_lossy().into_owned();
+			let path = freeName(dir, &name);
+			copyTree(template, &path).map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+			Ok(path)
+		}
+	}
+}
@@ -0,0 +1,132 @@
+use std::{fs, io};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::dataHome;
+use crate::datetime::{formatTime, localTime};
+use crate::fileops::moveTo;
+use crate::uri::pathToUri;
+
+/// The trash of the home directory, see https://specifications.freedesktop.org/trash-spec/trashspec-latest.html
+fn trashDir() -> Result<PathBuf, String> {
+	Ok(dataHome().ok_or("No home directory")?.join("Trash"))
+}
+
+/// The trash for a file on another filesystem than the home trash, at the top of that filesystem.
+/// Returns the trash directory and the top directory the paths in it are relative to.
+#[cfg(unix)]
+fn volumeTrashDir(path: &Path, homeTrash: &Path) -> Option<(PathBuf, PathBuf)> {
+	use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+	
+	let dev = fs::symlink_metadata(path.parent()?).ok()?.dev();
+	if fs::metadata(homeTrash).ok()?.dev() == dev {
+		return None;
+	}
+	let mut top = path.parent()?;
+	while let Some(parent) = top.parent() {
+		if fs::metadata(parent).map(|m| m.dev() != dev).unwrap_or(true) {
+			break;
+		}
+		top = parent;
+	}
+	let uid = unsafe { libc::getuid() };
+	
+	// An admin provided $topdir/.Trash has to have the sticky bit and must not be a link
+	let shared = top.join(".Trash");
+	if let Ok(meta) = fs::symlink_metadata(&shared) {
+		if meta.is_dir() && meta.permissions().mode() & 0o1000 != 0 {
+			let dir = shared.join(uid.to_string());
+			if fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir).is_ok() {
+				return Some((dir, top.to_path_buf()));
+			}
+		}
+	}
+	
+	let dir = top.join(format!(".Trash-{}", uid));
+	fs::DirBuilder::new().mode(0o700).create(&dir).ok();
+	let meta = fs::symlink_metadata(&dir).ok()?;
+	if !meta.is_dir() || meta.uid() != uid {
+		return None;
+	}
+	Some((dir, top.to_path_buf()))
+}
+
+/// Moves the file into the trash and returns where it ended up there. Files on other filesystems go to the trash
+/// at the top of their filesystem, and are only copied over to the home trash if that can't be used.
+#[cfg(unix)]
+pub fn trash(path: &Path) -> Result<PathBuf, String> {
+	let path = if path.is_absolute() { path.to_path_buf() } else { std::env::current_dir().map_err(|err| err.to_string())?.join(path) };
+	let name = path.file_name().ok_or("Can not trash a root directory")?.to_string_lossy().into_owned();
+	
+	let home = trashDir()?;
+	fs::create_dir_all(&home).map_err(|err| format!("{}: {}", home.display(), err))?;
+	let (dir, top) = match volumeTrashDir(&path, &home) {
+		Some((dir, top)) => { (dir, Some(top)) }
+		None => { (home, None) }
+	};
+	let files = dir.join("files");
+	let info = dir.join("info");
+	for d in [&files, &info] {
+		fs::create_dir_all(d).map_err(|err| format!("{}: {}", d.display(), err))?;
+	}
+	
+	// The info file is created first to claim the name, like the spec asks
+	let mut n = 1;
+	let (trashName, infoFile) = loop {
+		let candidate = if n == 1 { name.clone() } else { format!("{}.{}", name, n) };
+		let infoFile = info.join(format!("{}.trashinfo", candidate));
+		match fs::OpenOptions::new().write(true).create_new(true).open(&infoFile) {
+			Ok(mut f) => {
+				if fs::symlink_metadata(files.join(&candidate)).is_ok() {
+					drop(f);
+					fs::remove_file(&infoFile).ok();
+					n += 1;
+					continue;
+				}
+				let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+				let encoded = pathToUri(&path);
+				let encoded = encoded.strip_prefix("file://").unwrap_or(&encoded);
+				// Trashes at the top of a filesystem store paths relative to it, so they stay right wherever it is mounted
+				let stored = match top.as_ref().map(|t| pathToUri(t)) {
+					Some(t) => { encoded.strip_prefix(t.strip_prefix("file://").unwrap_or(&t)).map(|r| r.trim_start_matches('/')).unwrap_or(encoded) }
+					None => { encoded }
+				};
+				let data = format!("[Trash Info]\nPath={}\nDeletionDate={}\n", stored, formatTime(localTime(now), "%Y-%m-%dT%H:%M:%S"));
+				f.write_all(data.as_bytes()).map_err(|err| format!("{}: {}", infoFile.display(), err))?;
+				break (candidate, infoFile);
+			}
+			Err(err) if err.kind() == io::ErrorKind::AlreadyExists => { n += 1; }
+			Err(err) => { return Err(format!("{}: {}", infoFile.display(), err)); }
+		}
+	};
+	
+	let target = files.join(trashName);
+	if let Err(err) = moveTo(&path, &target) {
+		fs::remove_file(&infoFile).ok();
+		return Err(err.to_string());
+	}
+	Ok(target)
+}
+
+#[cfg(not(unix))]
+pub fn trash(_path: &Path) -> Result<PathBuf, String> {
+	Err("Moving to the trash is not supported on this platform".to_string())
+}
+
+/// Puts a trashed file back where it came from
+pub fn restore(trashed: &Path, original: &Path) -> Result<(), String> {
+	if fs::symlink_metadata(trashed).is_err() {
+		return Err(format!("{} is not in the trash anymore", original.display()));
+	}
+	if fs::symlink_metadata(original).is_ok() {
+		return Err(format!("{} exists again", original.display()));
+	}
+	moveTo(trashed, original).map_err(|err| format!("Failed to restore {}: {}", original.display(), err))?;
+	
+	if let (Some(name), Some(dir)) = (trashed.file_name(), trashed.parent().and_then(|p| p.parent())) {
+		let infoFile = dir.join("info").join(format!("{}.trashinfo", name.to_string_lossy()));
+		fs::remove_file(&infoFile).map_err(|err| println!("Failed to remove {}: {}", infoFile.display(), err)).ok();
+	}
+	Ok(())
+}
@@ -0,0 +1,47 @@
+use std::path::Path;
+
+/// Only local file uris can be turned into paths, "file://host/path" is accepted as long as the path is absolute
+pub fn uriToPath(uri: &str) -> Option<String> {
+	let rest = match uri.strip_prefix("file://") {
+		None => {
+			return if uri.starts_with('/') { Some(uri.to_string()) } else { None };
+		}
+		Some(r) => { r }
+	};
+	let path = &rest[rest.find('/')?..];
+	
+	let bytes = path.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == b'%' && i + 2 < bytes.len() {
+			let hex = (hexDigit(bytes[i + 1]), hexDigit(bytes[i + 2]));
+			if let (Some(hi), Some(lo)) = hex {
+				decoded.push(hi << 4 | lo);
+				i += 3;
+				continue;
+			}
+		}
+		decoded.push(bytes[i]);
+		i += 1;
+	}
+	
+	String::from_utf8(decoded).ok()
+}
+
+/// file:// uri with everything but unreserved characters and slashes percent encoded
+pub fn pathToUri(path: &Path) -> String {
+	let mut res = String::from("file://");
+	for b in path.to_string_lossy().bytes() {
+		if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+			res.push(b as char);
+		} else {
+			res.push_str(&format!("%{:02X}", b));
+		}
+	}
+	res
+}
+
+fn hexDigit(c: u8) -> Option<u8> {
+	(c as char).to_digit(16).map(|d| d as u8)
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::ImageFormat;
+
+use crate::rgba_img::RgbImg;
+
+/// Long animations are cut off rather than filling the memory
+const MAX_FRAMES: usize = 2000;
+const MAX_BYTES: usize = 1024 * 1024 * 1024;
+pub const SLIDESHOW_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Whether the viewer can show the file, going by its name like the image crate does
+pub fn isImage(path: &Path) -> bool {
+	ImageFormat::from_path(path).map(|f| f.can_read()).unwrap_or(false)
+}
+
+pub struct Loaded {
+	pub path: PathBuf,
+	/// Every frame with how long it is shown, a still image has one
+	pub frames: Result<Vec<(RgbImg, Duration)>, String>,
+}
+
+/// Decodes the image at full resolution in the background
+pub fn load(path: PathBuf) -> Receiver<Loaded> {
+	let (send, receiver) = channel();
+	thread::spawn(move || {
+		let frames = RgbImg::readFrames(&path.to_string_lossy(), None, MAX_FRAMES, MAX_BYTES);
+		let _ = send.send(Loaded { path, frames });
+	});
+	receiver
+}
+
+/// Which frame of an animation is due
+pub struct Playback {
+	delays: Vec<Duration>,
+	current: usize,
+	shownAt: Instant,
+}
+
+impl Playback {
+	pub fn new(delays: Vec<Duration>) -> Self {
+		Self { delays, current: 0, shownAt: Instant::now() }
+	}
+	
+	pub fn current(&self) -> usize { self.current }
+	
+	/// The new frame when it is time for the next one. Frames are skipped when the timer was late.
+	pub fn advance(&mut self) -> Option<usize> {
+		if self.delays.len() < 2 {
+			return None;
+		}
+		let before = self.current;
+		while self.shownAt.elapsed() >= self.delays[self.current] {
+			self.shownAt += self.delays[self.current];
+			self.current = (self.current + 1) % self.delays.len();
+		}
+		if self.current == before { None } else { Some(self.current) }
+	}
+}
@@ -0,0 +1,133 @@
+use std::fs;
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+use crate::config::{IgnoreMode, Settings};
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Visibility {
+	Shown,
+	Dimmed,
+	Hidden,
+}
+
+/// Decides how the entries of one directory are displayed
+pub struct EntryFilter {
+	showHidden: bool,
+	ignoreMode: IgnoreMode,
+	/// Deepest directory first
+	ignores: Vec<Gitignore>,
+	excludes: GlobSet,
+}
+
+impl EntryFilter {
+	pub fn forDir(dir: &Path, settings: &Settings) -> Self {
+		let ignores = if settings.ignoreMode == IgnoreMode::Show { vec![] } else { collectIgnores(dir) };
+		
+		let mut excludes = GlobSetBuilder::new();
+		for pattern in &settings.excludePatterns {
+			match Glob::new(pattern) {
+				Ok(g) => { excludes.add(g); }
+				Err(err) => { println!("Invalid exclude pattern {}: {}", pattern, err); }
+			}
+		}
+		
+		Self {
+			showHidden: settings.showHidden,
+			ignoreMode: settings.ignoreMode,
+			ignores,
+			excludes: excludes.build().unwrap_or_else(|_| GlobSet::empty()),
+		}
+	}
+	
+	pub fn classify(&self, path: &Path) -> Visibility {
+		// Excluded entries stay hidden even while hidden files are shown, that is what the patterns are for
+		if self.isExcluded(path) || (!self.showHidden && isHidden(path)) {
+			return Visibility::Hidden;
+		}
+		
+		if !self.ignores.is_empty() && self.isIgnored(path) {
+			return match self.ignoreMode {
+				IgnoreMode::Show => { Visibility::Shown }
+				IgnoreMode::Dim => { Visibility::Dimmed }
+				IgnoreMode::Hide => { Visibility::Hidden }
+			};
+		}
+		
+		Visibility::Shown
+	}
+	
+	fn isExcluded(&self, path: &Path) -> bool {
+		if self.excludes.is_empty() { return false; }
+		path.file_name().map(|n| self.excludes.is_match(n)).unwrap_or(false) || self.excludes.is_match(path)
+	}
+	
+	fn isIgnored(&self, path: &Path) -> bool {
+		let isDir = path.is_dir();
+		for ignore in &self.ignores {
+			match ignore.matched_path_or_any_parents(path, isDir) {
+				Match::None => {}
+				Match::Ignore(_) => { return true; }
+				Match::Whitelist(_) => { return false; }
+			}
+		}
+		false
+	}
+}
+
+/// .ignore files apply everywhere, .gitignore files only up to the root of the repository they are in
+fn collectIgnores(dir: &Path) -> Vec<Gitignore> {
+	let mut inRepo = dir.ancestors().any(|d| d.join(".git").exists());
+	
+	let mut res = vec![];
+	for d in dir.ancestors() {
+		let mut builder = GitignoreBuilder::new(d);
+		let mut any = false;
+		
+		let mut files = vec![d.join(".ignore")];
+		if inRepo {
+			files.push(d.join(".gitignore"));
+			files.push(d.join(".git").join("info").join("exclude"));
+		}
+		for f in files {
+			if f.is_file() {
+				any = true;
+				if let Some(err) = builder.add(&f) {
+					println!("Failed to parse {}: {}", f.display(), err);
+				}
+			}
+		}
+		
+		if any {
+			match builder.build() {
+				Ok(g) => { res.push(g); }
+				Err(err) => { println!("Failed to read ignore rules in {}: {}", d.display(), err); }
+			}
+		}
+		
+		if d.join(".git").exists() {
+			inRepo = false;
+		}
+	}
+	res
+}
+
+#[cfg(windows)]
+fn isHidden(path: &Path) -> bool {
+	use std::os::windows::fs::MetadataExt;
+	const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+	
+	startsWithDot(path) || fs::symlink_metadata(path).map(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0).unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+fn isHidden(path: &Path) -> bool {
+	startsWithDot(path)
+}
+
+fn startsWithDot(path: &Path) -> bool {
+	path.file_name().and_then(|n| n.to_str()).map(|n| n.starts_with('.')).unwrap_or(false)
+}